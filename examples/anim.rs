@@ -1,25 +1,23 @@
-// Create animation gif
-// convert -delay 20 -loop 0 output/*.pgm -sample 400% output.gif
-
-use std::io::{Read, Write};
-
-use slidart::Board;
+// Solve a .pa puzzle and write the animation to output.gif
 
 fn main() {
-    let filepath = std::env::args()
-        .skip(1)
-        .next()
-        .unwrap_or("min.pa".to_string());
-    let file = std::fs::File::open(filepath).unwrap();
-    let mut reader = std::io::BufReader::new(file);
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf).unwrap();
-    let l = buf.find("\n\n").unwrap();
-    let (header, body) = buf.split_at(l);
-    let board = string_to_board(header.trim());
-    let goal = string_to_board(body.trim());
-    let mut solver = slidart::Solver::new(goal, board);
-    let desire_len = 3000;
+    let filepath = std::env::args().nth(1).unwrap_or("min.pa".to_string());
+    let buf = std::fs::read_to_string(&filepath).unwrap();
+    let file = match slidart::format::parse(&buf) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("{}:{}", filepath, e);
+            std::process::exit(1);
+        }
+    };
+    let mut solver = match file.solver() {
+        Ok(solver) => solver,
+        Err(e) => {
+            eprintln!("{}: {}", filepath, e);
+            std::process::exit(1);
+        }
+    };
+    let desire_len = file.header.moves.unwrap_or(3000);
     // smile: 30
     // rainbow: 56
     // rainbow5: 120
@@ -32,8 +30,8 @@ fn main() {
     solver.check_mate_cutoff = 10;
     solver.random_walk = 0;
     solver.random_walk_len = 4;
-    solver.score_fn = Box::new(|board, distance| -distance * 100 - board.path.len() as isize * 1);
-    solver.distance_fn = Box::new(slidart::compute_distance4);
+    solver.score_fn = Box::new(|board, distance| -distance * 100 - board.path.len() as isize);
+    solver.set_heuristic(Box::new(slidart::Distance4));
     // solver.distance_fn = Box::new(|a, b| slidart::compute_distance1(a, b) + slidart::compute_distance2(a, b));
 
     while solver
@@ -53,52 +51,18 @@ fn main() {
         println!("path len: {}", result.path.len());
         // result.clone().print_history();
         // print_path(&result.path);
-        // println!("{}", board_to_pgm(&result));
-        output_pgms(&result);
-    }
-}
-
-fn string_to_board(s: &str) -> Board {
-    let mut cells = vec![];
-    let width = s.find("\n").unwrap();
-    for c in s.chars() {
-        match c {
-            '#' => cells.push(0),
-            '_' => cells.push(1),
-            '.' => cells.push(2),
-            '0'..='9' => cells.push((c as usize - '0' as usize) as u8),
-            _ => {}
-        }
-    }
-    Board::new(width, cells)
-}
-
-fn output_pgms(board: &Board) {
-    let mut board = board.clone();
-    std::fs::create_dir_all("output").unwrap();
-    while !board.path.is_empty() {
-        std::fs::File::create(format!("output/{:>04}.pgm", board.path.len()))
-            .unwrap()
-            .write(board_to_pgm(&board).as_bytes())
-            .unwrap();
-        board.undo();
-    }
-}
-
-fn board_to_pgm(board: &Board) -> String {
-    let height = board.cells.len() / board.width;
-    let mut s = format!(
-        "P2\n{} {}\n{}\n",
-        board.width,
-        height,
-        board.cells.iter().max().unwrap()
-    );
-    for y in 0..height {
-        for x in 0..board.width {
-            let c = board.cells[x + y * board.width];
-            s += &format!("{} ", c);
-        }
-        s.push('\n');
+        let output = std::fs::File::create("output.gif").unwrap();
+        slidart::render::gif::write_animation(
+            std::io::BufWriter::new(output),
+            &result,
+            &file.palette,
+            &slidart::render::anim::Animation::default(),
+            &slidart::render::gif::GifOptions {
+                delay: 5,
+                scale: 8,
+                ..Default::default()
+            },
+        )
+        .unwrap();
     }
-    s
 }
@@ -31,18 +31,28 @@ fn main() {
         ),
     };
 
-    for _ in 0..1000 {
-        let mut board = goal.clone();
-        board.shuffle(300, &mut rng);
-        let mut solver = Solver::new(goal.clone(), board);
-        solver.show_progress = false;
-        solver.open_node_limit = 10000;
-        solver.distance_fn = Box::new(slidart::compute_distance4);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    while attempt < 1000 {
+        let chunk = (1000 - attempt).min(threads * 4);
+        let mut solvers: Vec<_> = (0..chunk)
+            .map(|_| {
+                let mut board = goal.clone();
+                board.shuffle(300, &mut rng);
+                let mut solver = Solver::new(goal.clone(), board);
+                solver.show_progress = false;
+                solver.open_node_limit = 10000;
+                solver.set_heuristic(Box::new(slidart::Distance4));
+                solver
+            })
+            .collect();
 
-        attempt += 1;
-        if solver.search() {
-            succeeded += 1;
-            total_path_length += solver.result.unwrap().path.len();
+        attempt += chunk;
+        let found = slidart::search_batch(&mut solvers, threads);
+        for (solver, found) in solvers.into_iter().zip(found) {
+            if found {
+                succeeded += 1;
+                total_path_length += solver.result.unwrap().path.len();
+            }
         }
     }
 
@@ -0,0 +1,148 @@
+use std::sync::OnceLock;
+
+use crate::{Board, Heuristic};
+
+/// Sums, for every colour, the cost of the cheapest one-to-one matching of
+/// its tiles to its goal cells, each pair costing their Manhattan distance.
+/// Every move shifts one tile by one cell, so this never overestimates and
+/// is never below `Manhattan`.
+#[derive(Default)]
+pub struct Assignment {
+    /// Goal cells of every colour, indexed by colour. Built on first use if
+    /// `prepare` was not called.
+    goal_cells: OnceLock<Vec<Vec<usize>>>,
+}
+
+impl Assignment {
+    fn goal_cells(goal: &Board) -> Vec<Vec<usize>> {
+        let mut goal_cells = vec![vec![]; 256];
+        for (i, c) in goal.cells.iter().enumerate() {
+            if *c != 0 {
+                goal_cells[*c as usize].push(i);
+            }
+        }
+        goal_cells
+    }
+}
+
+impl Heuristic for Assignment {
+    fn name(&self) -> &'static str {
+        "assignment"
+    }
+
+    fn is_admissible(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        self.goal_cells = OnceLock::from(Self::goal_cells(goal));
+    }
+
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let mut heuristic = Assignment::default();
+        heuristic.prepare(goal);
+        Box::new(heuristic)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        let goal_cells = self.goal_cells.get_or_init(|| Self::goal_cells(goal));
+        let mut tiles = vec![vec![]; 256];
+        for (i, c) in board.cells.iter().enumerate() {
+            if *c != 0 {
+                tiles[*c as usize].push(i);
+            }
+        }
+        tiles
+            .iter()
+            .zip(goal_cells)
+            .filter(|(tiles, _)| !tiles.is_empty())
+            .map(|(tiles, goal_cells)| {
+                let cost: Vec<Vec<isize>> = tiles
+                    .iter()
+                    .map(|t| {
+                        goal_cells
+                            .iter()
+                            .map(|g| goal.index_distance(*t, *g))
+                            .collect()
+                    })
+                    .collect();
+                min_cost_assignment(&cost)
+            })
+            .sum()
+    }
+}
+
+/// Returns the cost of the cheapest perfect matching of rows to columns of the
+/// square matrix `cost`, using the Hungarian method in O(n^3).
+pub fn min_cost_assignment(cost: &[Vec<isize>]) -> isize {
+    let n = cost.len();
+    // Potentials and matching use 1-based indices; column 0 is a sentinel.
+    let mut u = vec![0; n + 1];
+    let mut v = vec![0; n + 1];
+    let mut row_of = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+    for i in 1..=n {
+        row_of[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![isize::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = row_of[j0];
+            let mut delta = isize::MAX;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                if reduced < min_v[j] {
+                    min_v[j] = reduced;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if row_of[j0] == 0 {
+                break;
+            }
+        }
+        while j0 != 0 {
+            let j1 = way[j0];
+            row_of[j0] = row_of[j1];
+            j0 = j1;
+        }
+    }
+    (1..=n).map(|j| cost[row_of[j] - 1][j - 1]).sum()
+}
+
+#[test]
+fn test_assignment() {
+    assert_eq!(min_cost_assignment(&[]), 0);
+    assert_eq!(
+        min_cost_assignment(&[vec![4, 1, 3], vec![2, 0, 5], vec![3, 2, 2]]),
+        5
+    );
+
+    // The stray 2s are next to goal cells that other 2s already occupy.
+    let goal = Board::new(3, vec![1, 2, 2, 1, 2, 2, 0, 2, 2]);
+    let board = Board::new(3, vec![2, 2, 1, 2, 2, 1, 0, 2, 2]);
+    let mut heuristic = Assignment::default();
+    assert_eq!(heuristic.evaluate(&board, &goal), 8);
+    heuristic.prepare(&goal);
+    assert_eq!(heuristic.evaluate(&board, &goal), 8);
+    let mut manhattan = crate::Manhattan::default();
+    manhattan.prepare(&goal);
+    assert!(heuristic.evaluate(&board, &goal) >= manhattan.evaluate(&board, &goal));
+}
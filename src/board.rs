@@ -3,20 +3,157 @@ use rand_core::RngCore;
 #[derive(Clone)]
 pub struct Board {
     pub width: usize,
+    /// Writing to `cells` directly leaves the hash stale until `rehash` is
+    /// called; `move_to`, `undo` and `swap_cells` keep it up to date.
     pub cells: Vec<u8>,
     pub empty_cell: usize,
     pub path: Vec<usize>,
+    /// Zobrist hash of `cells`.
+    hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    ZeroWidth,
+    /// The number of cells is not a multiple of the width.
+    RaggedSize {
+        len: usize,
+        width: usize,
+    },
+    NoEmptyCell,
+    MultipleEmptyCells,
+    /// Two boards that should be compared have different shapes.
+    SizeMismatch,
+    /// Two boards do not hold the same multiset of tiles.
+    TileMismatch,
+    /// No sequence of moves turns one board into the other.
+    Unreachable,
 }
 
 impl Board {
     pub fn new(width: usize, cells: Vec<u8>) -> Self {
-        let empty_cell = cells.iter().position(|c| *c == 0).unwrap();
-        Self {
+        Self::try_new(width, cells).unwrap()
+    }
+
+    pub fn try_new(width: usize, cells: Vec<u8>) -> Result<Self, BoardError> {
+        if width == 0 {
+            return Err(BoardError::ZeroWidth);
+        }
+        if cells.len() % width != 0 {
+            return Err(BoardError::RaggedSize {
+                len: cells.len(),
+                width,
+            });
+        }
+        let mut empty_cells = cells.iter().enumerate().filter(|(_, c)| **c == 0);
+        let empty_cell = match (empty_cells.next(), empty_cells.next()) {
+            (Some((i, _)), None) => i,
+            (None, _) => return Err(BoardError::NoEmptyCell),
+            (Some(_), Some(_)) => return Err(BoardError::MultipleEmptyCells),
+        };
+        let mut board = Self {
             width,
             cells,
             empty_cell,
             path: vec![],
+            hash: 0,
+        };
+        board.rehash();
+        Ok(board)
+    }
+
+    /// Hash of the cells that is cheap to keep up to date as tiles move, so
+    /// equal boards hash equally whatever their paths.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Swaps the contents of cells `a` and `b`, keeping the hash and the
+    /// empty cell up to date. The path is left as it is.
+    pub fn swap_cells(&mut self, a: usize, b: usize) {
+        let (ca, cb) = (self.cells[a], self.cells[b]);
+        self.hash ^= zobrist(a, ca) ^ zobrist(b, cb) ^ zobrist(a, cb) ^ zobrist(b, ca);
+        self.cells.swap(a, b);
+        if self.empty_cell == a {
+            self.empty_cell = b;
+        } else if self.empty_cell == b {
+            self.empty_cell = a;
+        }
+    }
+
+    /// Recomputes the hash; needed after changing `cells` directly.
+    pub fn rehash(&mut self) {
+        self.hash = self
+            .cells
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (i, c)| hash ^ zobrist(i, *c));
+    }
+
+    /// Checks that `other` has the same shape and the same tiles as `self`.
+    pub fn check_compatible(&self, other: &Board) -> Result<(), BoardError> {
+        if self.width != other.width || self.cells.len() != other.cells.len() {
+            return Err(BoardError::SizeMismatch);
         }
+        let mut c1 = self.cells.clone();
+        c1.sort_unstable();
+        let mut c2 = other.cells.clone();
+        c2.sort_unstable();
+        if c1 != c2 {
+            return Err(BoardError::TileMismatch);
+        }
+        Ok(())
+    }
+
+    /// Returns whether `self` can be turned into `goal` by sliding tiles.
+    ///
+    /// Every move swaps the empty cell with a tile, so on boards of at least
+    /// 2x2 a configuration is reachable exactly when the parity of the
+    /// permutation between the boards matches the parity of the empty cell's
+    /// distance. A colour appearing twice lets the permutation absorb a swap
+    /// of equal tiles, so then any arrangement is reachable. Boards with a
+    /// single row or column can never reorder their tiles.
+    pub fn is_reachable_from(&self, goal: &Board) -> bool {
+        if self.check_compatible(goal).is_err() {
+            return false;
+        }
+        let height = self.cells.len() / self.width;
+        if self.width == 1 || height == 1 {
+            let tiles = |b: &Board| {
+                b.cells
+                    .iter()
+                    .filter(|c| **c != 0)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            return tiles(self) == tiles(goal);
+        }
+
+        let mut position = [usize::MAX; 256];
+        for (i, c) in goal.cells.iter().enumerate() {
+            if position[*c as usize] != usize::MAX {
+                return true;
+            }
+            position[*c as usize] = i;
+        }
+
+        let permutation: Vec<usize> = self.cells.iter().map(|c| position[*c as usize]).collect();
+        let mut visited = vec![false; permutation.len()];
+        let mut cycles = 0;
+        for i in 0..permutation.len() {
+            if visited[i] {
+                continue;
+            }
+            cycles += 1;
+            let mut j = i;
+            while !visited[j] {
+                visited[j] = true;
+                j = permutation[j];
+            }
+        }
+        let swaps = permutation.len() - cycles;
+        let distance = self.index_distance(self.empty_cell, goal.empty_cell) as usize;
+        swaps % 2 == distance % 2
     }
 
     pub fn shuffle(&mut self, n: usize, rng: &mut impl RngCore) {
@@ -46,6 +183,21 @@ impl Board {
         println!();
     }
 
+    /// Like `print`, but shows each cell by its symbol in `palette`.
+    pub fn print_with(&self, palette: &crate::Palette) {
+        for y in 0..self.cells.len() / self.width {
+            for x in 0..self.width {
+                let c = self.cells[y * self.width + x];
+                match palette.symbol(c) {
+                    Some(symbol) => print!("{:>2} ", symbol),
+                    None => print!("{:>2} ", c),
+                }
+            }
+            println!();
+        }
+        println!();
+    }
+
     pub fn print_history(mut self) {
         while !self.path.is_empty() {
             self.print();
@@ -98,6 +250,8 @@ impl Board {
     #[inline]
     pub fn move_to(&mut self, next_empty_cell: usize) {
         self.path.push(self.empty_cell);
+        let color = self.cells[next_empty_cell];
+        self.hash ^= zobrist(self.empty_cell, color) ^ zobrist(next_empty_cell, color);
         self.cells[self.empty_cell] = self.cells[next_empty_cell];
         self.cells[next_empty_cell] = 0;
         self.empty_cell = next_empty_cell;
@@ -106,6 +260,8 @@ impl Board {
     #[inline]
     pub fn undo(&mut self) {
         let last_cell = self.path.pop().unwrap();
+        let color = self.cells[last_cell];
+        self.hash ^= zobrist(self.empty_cell, color) ^ zobrist(last_cell, color);
         self.cells[self.empty_cell] = self.cells[last_cell];
         self.cells[last_cell] = 0;
         self.empty_cell = last_cell;
@@ -123,3 +279,85 @@ impl Board {
         (index % self.width, index / self.width)
     }
 }
+
+/// Random key of `color` at `cell`, mixed with splitmix64 rather than looked
+/// up so that no table has to be sized for the board. The empty cell adds
+/// nothing.
+fn zobrist(cell: usize, color: u8) -> u64 {
+    if color == 0 {
+        return 0;
+    }
+    let mut z = ((cell as u64) << 8 | color as u64).wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BoardError::ZeroWidth => write!(f, "board width is zero"),
+            BoardError::RaggedSize { len, width } => {
+                write!(f, "{} cells do not fill rows of width {}", len, width)
+            }
+            BoardError::NoEmptyCell => write!(f, "board has no empty cell"),
+            BoardError::MultipleEmptyCells => write!(f, "board has several empty cells"),
+            BoardError::SizeMismatch => write!(f, "boards have different sizes"),
+            BoardError::TileMismatch => write!(f, "boards do not hold the same tiles"),
+            BoardError::Unreachable => write!(f, "goal board cannot be reached"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+#[test]
+fn test_try_new() {
+    assert!(Board::try_new(2, vec![0, 1, 2, 1]).is_ok());
+    assert_eq!(Board::try_new(0, vec![]).err(), Some(BoardError::ZeroWidth));
+    assert_eq!(
+        Board::try_new(3, vec![0, 1, 2, 1]).err(),
+        Some(BoardError::RaggedSize { len: 4, width: 3 })
+    );
+    assert_eq!(
+        Board::try_new(2, vec![1, 1, 2, 1]).err(),
+        Some(BoardError::NoEmptyCell)
+    );
+    assert_eq!(
+        Board::try_new(2, vec![0, 1, 0, 1]).err(),
+        Some(BoardError::MultipleEmptyCells)
+    );
+    let board = Board::new(2, vec![0, 1, 2, 1]);
+    assert_eq!(
+        board.check_compatible(&Board::new(2, vec![1, 0, 2, 2])),
+        Err(BoardError::TileMismatch)
+    );
+    assert_eq!(
+        board.check_compatible(&Board::new(4, vec![1, 0, 2, 1])),
+        Err(BoardError::SizeMismatch)
+    );
+}
+
+#[test]
+fn test_is_reachable_from() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(3, vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    let mut board = goal.clone();
+    board.shuffle(50, &mut rng);
+    assert!(board.is_reachable_from(&goal));
+
+    let (a, b) = if board.empty_cell < 2 { (2, 3) } else { (0, 1) };
+    board.swap_cells(a, b);
+    assert!(!board.is_reachable_from(&goal));
+    let fresh = Board::new(3, board.cells.to_vec());
+    assert_eq!(board.zobrist_hash(), fresh.zobrist_hash());
+    board.swap_cells(board.empty_cell, 4);
+    assert_eq!(board.cells[board.empty_cell], 0);
+
+    let goal = Board::new(2, vec![0, 1, 1, 2]);
+    assert!(Board::new(2, vec![0, 2, 1, 1]).is_reachable_from(&goal));
+
+    let goal = Board::new(3, vec![0, 1, 2]);
+    assert!(Board::new(3, vec![1, 0, 2]).is_reachable_from(&goal));
+    assert!(!Board::new(3, vec![2, 0, 1]).is_reachable_from(&goal));
+}
@@ -0,0 +1,702 @@
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use rand_pcg::Pcg32;
+
+use crate::packed::word_count;
+use crate::pattern_database::{read_bytes, read_u32};
+use crate::solver::{Bidirectional, PathTree, ROOT};
+use crate::{
+    bits_per_cell, heuristic_by_name, Board, Heuristic, PackedBoard, SearchMode, SearchReport,
+    Solver, State, Termination,
+};
+
+const MAGIC: &[u8; 5] = b"SLCKP";
+const VERSION: u8 = 3;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    InvalidFormat,
+    UnsupportedVersion(u8),
+    /// The checkpoint names a heuristic this build does not have.
+    UnknownHeuristic(String),
+    /// The search was saved with another heuristic, or with other tables
+    /// for the named one.
+    HeuristicMismatch(String),
+    /// IDA* keeps its bound and path on the stack, so it cannot be saved.
+    UnsupportedMode(SearchMode),
+}
+
+impl Solver<Pcg32> {
+    /// Writes everything needed to resume the search with `read_checkpoint`:
+    /// the boards, frontiers, closed sets, counters, settings and the state
+    /// of `rng`. `score_fn` cannot be saved; the heuristic is saved by name
+    /// and fingerprint, as its tables may not follow from the goal alone.
+    pub fn write_checkpoint(&self, mut writer: impl Write) -> Result<(), CheckpointError> {
+        if self.mode == SearchMode::IdaStar {
+            return Err(CheckpointError::UnsupportedMode(self.mode));
+        }
+        let w = &mut writer;
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_board(w, &self.goal)?;
+        write_board(w, &self.start)?;
+        write_name(w, self.heuristic.name())?;
+        write_u64(w, self.heuristic.fingerprint())?;
+        w.write_all(&[mode_to_u8(self.mode), self.show_progress as u8])?;
+        write_u64(w, self.weight.to_bits())?;
+        write_u64(w, self.weight_step.to_bits())?;
+        for value in [
+            self.beam_width,
+            self.open_node_limit,
+            self.progress_interval,
+            self.check_mate_cutoff,
+            self.random_walk,
+            self.random_walk_len,
+        ] {
+            write_usize(w, value)?;
+        }
+
+        let rng = bincode::serialize(&self.rng).expect("the rng is plain data");
+        write_u32(w, rng.len() as u32)?;
+        w.write_all(&rng)?;
+
+        write_usize(w, self.open_node_count)?;
+        write_report(w, &self.report)?;
+        w.write_all(&[self.result.is_some() as u8])?;
+        if let Some(result) = &self.result {
+            write_board(w, result)?;
+        }
+
+        write_u64(w, self.paths.nodes.len() as u64)?;
+        for (parent, cell) in &self.paths.nodes {
+            write_u64(w, *parent)?;
+            write_u32(w, *cell)?;
+        }
+        // The heap's own order is kept, so that ties pop the same way after
+        // loading.
+        write_states(w, self.states.iter())?;
+        write_state(w, &self.best_state)?;
+        write_usize(w, self.closed.len())?;
+        for (key, moves) in &self.closed {
+            write_words(w, key.words())?;
+            write_usize(w, *moves)?;
+        }
+        write_u32(w, self.beam_history.len() as u32)?;
+        for layer in &self.beam_history {
+            write_usize(w, layer.len())?;
+            for key in layer {
+                write_words(w, key.words())?;
+            }
+        }
+        w.write_all(&[self.bidirectional.is_some() as u8])?;
+        if let Some(search) = &self.bidirectional {
+            for states in &search.states {
+                write_states(w, states.iter())?;
+            }
+            for seen in &search.seen {
+                write_usize(w, seen.len())?;
+                for (key, node) in seen {
+                    write_words(w, key.words())?;
+                    write_u64(w, *node)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a solver written by `write_checkpoint`. Its heuristic is looked
+    /// up by name and prepared again, and must come out with the saved
+    /// fingerprint; `progress`, `deadline` and `cancel_token` are left at
+    /// their defaults, and the frontier is rescored with the default
+    /// `score_fn` until `set_score_fn` replaces it.
+    pub fn read_checkpoint(reader: impl Read) -> Result<Self, CheckpointError> {
+        Self::read(reader, None)
+    }
+
+    /// Like `read_checkpoint`, but resumes with `heuristic`, e.g. a pattern
+    /// database loaded from a file, which must match the saved one.
+    pub fn read_checkpoint_with(
+        reader: impl Read,
+        heuristic: Box<dyn Heuristic>,
+    ) -> Result<Self, CheckpointError> {
+        Self::read(reader, Some(heuristic))
+    }
+
+    fn read(
+        mut reader: impl Read,
+        heuristic: Option<Box<dyn Heuristic>>,
+    ) -> Result<Self, CheckpointError> {
+        let r = &mut reader;
+        let mut magic = [0; 5];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::InvalidFormat);
+        }
+        let version = read_bytes(r, 1)?[0];
+        if version != VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        let goal = read_board(r, None)?;
+        let start = read_board(r, Some(&goal))?;
+        let bits = bits_per_cell(&goal);
+        let packed = Reader { goal: &goal, bits };
+        let heuristic = read_heuristic(r, &goal, heuristic)?;
+        let flags = read_bytes(r, 2)?;
+        let mode = mode_from_u8(flags[0])?;
+        let weight = f64::from_bits(read_u64(r)?);
+        let weight_step = f64::from_bits(read_u64(r)?);
+        let beam_width = read_usize(r)?;
+        let open_node_limit = read_usize(r)?;
+        let progress_interval = read_usize(r)?;
+        let check_mate_cutoff = read_usize(r)?;
+        let random_walk = read_usize(r)?;
+        let random_walk_len = read_usize(r)?;
+
+        let rng_len = read_u32(r)? as usize;
+        let rng = bincode::deserialize(&read_bytes(r, rng_len)?)
+            .map_err(|_| CheckpointError::InvalidFormat)?;
+
+        let open_node_count = read_usize(r)?;
+        let report = read_report(r)?;
+        let result = match read_bytes(r, 1)?[0] {
+            0 => None,
+            _ => Some(read_board(r, Some(&goal))?),
+        };
+
+        let node_count = read_u64(r)?;
+        let mut paths = PathTree::default();
+        for i in 0..node_count {
+            let parent = read_u64(r)?;
+            let cell = read_u32(r)?;
+            // Parents always come first.
+            if parent != ROOT && parent >= i || cell as usize >= goal.cells.len() {
+                return Err(CheckpointError::InvalidFormat);
+            }
+            paths.nodes.push((parent, cell));
+        }
+        let states = packed.read_states(r, node_count)?;
+        let best_state = packed.read_state(r, node_count)?;
+        let mut closed = HashMap::new();
+        for _ in 0..read_usize(r)? {
+            let key = packed.read_key(r)?;
+            closed.insert(key, read_usize(r)?);
+        }
+        let mut beam_history = VecDeque::new();
+        for _ in 0..read_u32(r)? {
+            let mut layer = HashSet::new();
+            for _ in 0..read_usize(r)? {
+                layer.insert(packed.read_key(r)?);
+            }
+            beam_history.push_back(layer);
+        }
+        let bidirectional = match read_bytes(r, 1)?[0] {
+            0 => None,
+            _ => {
+                let backward_heuristic = heuristic.for_goal(&start);
+                let states = [
+                    packed.read_states(r, node_count)?,
+                    packed.read_states(r, node_count)?,
+                ];
+                let mut seen = [HashMap::new(), HashMap::new()];
+                for seen in &mut seen {
+                    for _ in 0..read_usize(r)? {
+                        let key = packed.read_key(r)?;
+                        let node = read_u64(r)?;
+                        if node != ROOT && node >= node_count {
+                            return Err(CheckpointError::InvalidFormat);
+                        }
+                        seen.insert(key, node);
+                    }
+                }
+                Some(Bidirectional {
+                    backward_heuristic,
+                    states,
+                    seen,
+                })
+            }
+        };
+
+        let mut solver = Self {
+            rng,
+            goal,
+            states,
+            closed,
+            best_state,
+            open_node_count,
+            open_node_limit,
+            deadline: None,
+            cancel_token: None,
+            progress: None,
+            progress_interval,
+            show_progress: flags[1] != 0,
+            measure_time: false,
+            check_mate_cutoff,
+            random_walk,
+            random_walk_len,
+            score_fn: Box::new(|_, distance| -distance),
+            heuristic,
+            mode,
+            weight,
+            weight_step,
+            beam_width,
+            result,
+            report,
+            header: Default::default(),
+            palette: Default::default(),
+            start,
+            paths,
+            bits,
+            beam_history,
+            bidirectional,
+            started: None,
+            next_deadline_check: 0,
+        };
+        solver.set_score_fn(Box::new(|_, distance| -distance));
+        Ok(solver)
+    }
+
+    /// Writes the checkpoint to a temporary file first, so that a crash
+    /// while saving leaves the previous checkpoint intact.
+    pub fn save_checkpoint(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), CheckpointError> {
+        if self.mode == SearchMode::IdaStar {
+            return Err(CheckpointError::UnsupportedMode(self.mode));
+        }
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let file = std::fs::File::create(&temporary)?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.write_checkpoint(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load_checkpoint(path: impl AsRef<std::path::Path>) -> Result<Self, CheckpointError> {
+        let file = std::fs::File::open(path)?;
+        Self::read_checkpoint(std::io::BufReader::new(file))
+    }
+
+    pub fn load_checkpoint_with(
+        path: impl AsRef<std::path::Path>,
+        heuristic: Box<dyn Heuristic>,
+    ) -> Result<Self, CheckpointError> {
+        let file = std::fs::File::open(path)?;
+        Self::read_checkpoint_with(std::io::BufReader::new(file), heuristic)
+    }
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_usize(writer: &mut impl Write, value: usize) -> std::io::Result<()> {
+    write_u64(writer, value as u64)
+}
+
+fn write_name(writer: &mut impl Write, name: &str) -> std::io::Result<()> {
+    writer.write_all(&[name.len() as u8])?;
+    writer.write_all(name.as_bytes())
+}
+
+/// Writes a board with its path.
+fn write_board(writer: &mut impl Write, board: &Board) -> std::io::Result<()> {
+    write_u32(writer, board.width as u32)?;
+    write_u32(writer, board.cells.len() as u32)?;
+    writer.write_all(&board.cells)?;
+    write_u32(writer, board.path.len() as u32)?;
+    for cell in &board.path {
+        write_u32(writer, *cell as u32)?;
+    }
+    Ok(())
+}
+
+fn write_words(writer: &mut impl Write, words: &[u64]) -> std::io::Result<()> {
+    for word in words {
+        write_u64(writer, *word)?;
+    }
+    Ok(())
+}
+
+fn write_state(writer: &mut impl Write, state: &State) -> std::io::Result<()> {
+    write_words(writer, state.board.words())?;
+    write_u64(writer, state.node)?;
+    write_usize(writer, state.moves)?;
+    write_u64(writer, state.score as u64)?;
+    writer.write_all(&[state.distance.is_some() as u8])?;
+    write_u64(writer, state.distance.unwrap_or(0) as u64)
+}
+
+fn write_states<'a>(
+    writer: &mut impl Write,
+    states: impl ExactSizeIterator<Item = &'a State>,
+) -> std::io::Result<()> {
+    write_usize(writer, states.len())?;
+    for state in states {
+        write_state(writer, state)?;
+    }
+    Ok(())
+}
+
+fn write_report(writer: &mut impl Write, report: &SearchReport) -> std::io::Result<()> {
+    for value in [
+        report.expanded,
+        report.generated,
+        report.duplicates,
+        report.peak_frontier,
+        report.peak_closed,
+    ] {
+        write_usize(writer, value)?;
+    }
+    for time in [
+        report.heuristic_time,
+        report.check_mate_time,
+        report.elapsed,
+    ] {
+        write_u64(writer, time.as_nanos() as u64)?;
+    }
+    write_usize(writer, report.solution_length.unwrap_or(usize::MAX))?;
+    writer.write_all(&[report.termination.map_or(0, termination_to_u8)])
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    Ok(u64::from_le_bytes(
+        read_bytes(reader, 8)?.try_into().unwrap(),
+    ))
+}
+
+fn read_usize(reader: &mut impl Read) -> Result<usize, CheckpointError> {
+    usize::try_from(read_u64(reader)?).map_err(|_| CheckpointError::InvalidFormat)
+}
+
+/// Reads a board, which must have the shape and tiles of `goal` if given.
+fn read_board(reader: &mut impl Read, goal: Option<&Board>) -> Result<Board, CheckpointError> {
+    let width = read_u32(reader)? as usize;
+    let len = read_u32(reader)? as usize;
+    let shape = goal.map_or((width, len), |g| (g.width, g.cells.len()));
+    if width == 0 || len % width != 0 || (width, len) != shape {
+        return Err(CheckpointError::InvalidFormat);
+    }
+    let cells = read_bytes(reader, len)?;
+    let mut board = Board::try_new(width, cells).map_err(|_| CheckpointError::InvalidFormat)?;
+    for _ in 0..read_u32(reader)? {
+        let cell = read_u32(reader)? as usize;
+        if cell >= len {
+            return Err(CheckpointError::InvalidFormat);
+        }
+        board.path.push(cell);
+    }
+    if let Some(goal) = goal {
+        goal.check_compatible(&board)
+            .map_err(|_| CheckpointError::InvalidFormat)?;
+    }
+    Ok(board)
+}
+
+/// Prepares `heuristic`, or the saved one looked up by name, for `goal` and
+/// checks that it matches the saved name and fingerprint.
+fn read_heuristic(
+    reader: &mut impl Read,
+    goal: &Board,
+    heuristic: Option<Box<dyn Heuristic>>,
+) -> Result<Box<dyn Heuristic>, CheckpointError> {
+    let len = read_bytes(reader, 1)?[0] as usize;
+    let name =
+        String::from_utf8(read_bytes(reader, len)?).map_err(|_| CheckpointError::InvalidFormat)?;
+    let fingerprint = read_u64(reader)?;
+    let mut heuristic = match heuristic {
+        Some(heuristic) => heuristic,
+        None => heuristic_by_name(&name)
+            .ok_or_else(|| CheckpointError::UnknownHeuristic(name.clone()))?,
+    };
+    if heuristic.name() != name {
+        return Err(CheckpointError::HeuristicMismatch(name));
+    }
+    heuristic.prepare(goal);
+    if heuristic.fingerprint() != fingerprint {
+        return Err(CheckpointError::HeuristicMismatch(name));
+    }
+    Ok(heuristic)
+}
+
+fn read_report(reader: &mut impl Read) -> Result<SearchReport, CheckpointError> {
+    let mut counts = [0; 5];
+    for count in &mut counts {
+        *count = read_usize(reader)?;
+    }
+    let [expanded, generated, duplicates, peak_frontier, peak_closed] = counts;
+    let mut times = [Duration::ZERO; 3];
+    for time in &mut times {
+        *time = Duration::from_nanos(read_u64(reader)?);
+    }
+    let [heuristic_time, check_mate_time, elapsed] = times;
+    let solution_length = Some(read_usize(reader)?).filter(|l| *l != usize::MAX);
+    let termination = match read_bytes(reader, 1)?[0] {
+        0 => None,
+        t => Some(termination_from_u8(t)?),
+    };
+    Ok(SearchReport {
+        expanded,
+        generated,
+        duplicates,
+        peak_frontier,
+        peak_closed,
+        heuristic_time,
+        check_mate_time,
+        elapsed,
+        solution_length,
+        termination,
+    })
+}
+
+/// Reads the packed boards of a checkpoint, which all have the goal's shape
+/// and tiles.
+struct Reader<'a> {
+    goal: &'a Board,
+    bits: u32,
+}
+
+impl Reader<'_> {
+    fn read_board(&self, reader: &mut impl Read) -> Result<Board, CheckpointError> {
+        let len = self.goal.cells.len();
+        let words = (0..word_count(len, self.bits))
+            .map(|_| read_u64(reader))
+            .collect::<std::io::Result<_>>()?;
+        let board = PackedBoard::unpack_board(words, self.goal.width, len, self.bits)
+            .map_err(|_| CheckpointError::InvalidFormat)?;
+        self.goal
+            .check_compatible(&board)
+            .map_err(|_| CheckpointError::InvalidFormat)?;
+        Ok(board)
+    }
+
+    fn read_key(&self, reader: &mut impl Read) -> Result<PackedBoard, CheckpointError> {
+        Ok(PackedBoard::new(&self.read_board(reader)?, self.bits))
+    }
+
+    fn read_state(&self, reader: &mut impl Read, nodes: u64) -> Result<State, CheckpointError> {
+        let board = self.read_key(reader)?;
+        let node = read_u64(reader)?;
+        if node != ROOT && node >= nodes {
+            return Err(CheckpointError::InvalidFormat);
+        }
+        let moves = read_usize(reader)?;
+        let score = read_u64(reader)? as isize;
+        let known = read_bytes(reader, 1)?[0] != 0;
+        let distance = read_u64(reader)? as isize;
+        Ok(State {
+            board,
+            node,
+            moves,
+            score,
+            distance: Some(distance).filter(|_| known),
+        })
+    }
+
+    fn read_states(
+        &self,
+        reader: &mut impl Read,
+        nodes: u64,
+    ) -> Result<BinaryHeap<State>, CheckpointError> {
+        let len = read_usize(reader)?;
+        let mut states = Vec::with_capacity(len.min(1 << 20));
+        for _ in 0..len {
+            states.push(self.read_state(reader, nodes)?);
+        }
+        // Already in heap order, so this moves nothing.
+        Ok(states.into())
+    }
+}
+
+fn mode_to_u8(mode: SearchMode) -> u8 {
+    match mode {
+        SearchMode::Greedy => 0,
+        SearchMode::AStar => 1,
+        SearchMode::IdaStar => 2,
+        SearchMode::Anytime => 3,
+        SearchMode::Beam => 4,
+        SearchMode::Bidirectional => 5,
+    }
+}
+
+fn mode_from_u8(value: u8) -> Result<SearchMode, CheckpointError> {
+    Ok(match value {
+        0 => SearchMode::Greedy,
+        1 => SearchMode::AStar,
+        2 => SearchMode::IdaStar,
+        3 => SearchMode::Anytime,
+        4 => SearchMode::Beam,
+        5 => SearchMode::Bidirectional,
+        _ => return Err(CheckpointError::InvalidFormat),
+    })
+}
+
+/// 0 stands for no termination yet.
+fn termination_to_u8(termination: Termination) -> u8 {
+    match termination {
+        Termination::Solved => 1,
+        Termination::NodeLimit => 2,
+        Termination::Deadline => 3,
+        Termination::Cancelled => 4,
+        Termination::Exhausted => 5,
+    }
+}
+
+fn termination_from_u8(value: u8) -> Result<Termination, CheckpointError> {
+    Ok(match value {
+        1 => Termination::Solved,
+        2 => Termination::NodeLimit,
+        3 => Termination::Deadline,
+        4 => Termination::Cancelled,
+        5 => Termination::Exhausted,
+        _ => return Err(CheckpointError::InvalidFormat),
+    })
+}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(e: std::io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{}", e),
+            CheckpointError::InvalidFormat => write!(f, "not a search checkpoint"),
+            CheckpointError::UnsupportedVersion(v) => {
+                write!(f, "unsupported checkpoint version {}", v)
+            }
+            CheckpointError::UnknownHeuristic(name) => write!(f, "unknown heuristic {}", name),
+            CheckpointError::HeuristicMismatch(name) => {
+                write!(f, "saved with a different {} heuristic", name)
+            }
+            CheckpointError::UnsupportedMode(mode) => {
+                write!(f, "cannot save a {:?} search", mode)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+#[test]
+fn test_checkpoint() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    board.shuffle(100, &mut rng);
+    board.path.clear();
+
+    for mode in [
+        SearchMode::Greedy,
+        SearchMode::AStar,
+        SearchMode::Anytime,
+        SearchMode::Beam,
+        SearchMode::Bidirectional,
+    ] {
+        let score_fn = || -> Box<dyn Fn(&Board, isize) -> isize + Send + Sync> {
+            Box::new(|board, distance| -distance * 100 - board.path.len() as isize)
+        };
+        let new_solver = || {
+            let mut solver = Solver::new(goal.clone(), board.clone());
+            solver.mode = mode;
+            solver.beam_width = 20;
+            solver.open_node_limit = 30;
+            // Random walks draw from `rng`, which has to be saved as is.
+            solver.random_walk = 1;
+            solver.set_score_fn(score_fn());
+            solver
+        };
+        let mut solver = new_solver();
+        let mut unsaved = new_solver();
+        assert!(!solver.search());
+        assert!(!unsaved.search());
+
+        let mut buf = vec![];
+        solver.write_checkpoint(&mut buf).unwrap();
+        let mut loaded = Solver::<rand_pcg::Pcg32>::read_checkpoint(&buf[..]).unwrap();
+        loaded.set_score_fn(score_fn());
+        assert_eq!(loaded.open_node_count, solver.open_node_count);
+        assert_eq!(loaded.closed, solver.closed);
+        for solver in [&mut solver, &mut unsaved, &mut loaded] {
+            solver.open_node_limit = usize::MAX;
+            assert!(solver.search(), "{:?}", mode);
+        }
+        let result = solver.result.unwrap();
+        let loaded_result = loaded.result.unwrap();
+        assert_eq!(result.path, loaded_result.path, "{:?}", mode);
+        assert_eq!(result.path, unsaved.result.unwrap().path, "{:?}", mode);
+        assert_eq!(solver.open_node_count, loaded.open_node_count);
+        crate::verify(&board, &goal, &loaded_result.moves()).unwrap();
+
+        buf[5] = VERSION + 1;
+        assert!(matches!(
+            Solver::<rand_pcg::Pcg32>::read_checkpoint(&buf[..]),
+            Err(CheckpointError::UnsupportedVersion(_))
+        ));
+    }
+
+    let mut solver = Solver::<rand_pcg::Pcg32>::new(goal, board);
+    solver.mode = SearchMode::IdaStar;
+    assert!(matches!(
+        solver.write_checkpoint(vec![]),
+        Err(CheckpointError::UnsupportedMode(SearchMode::IdaStar))
+    ));
+
+    // A packed board of the goal's shape but with other tiles.
+    let goal = Board::new(2, vec![0, 1, 1, 2]);
+    let bits = bits_per_cell(&goal);
+    let mut buf = vec![];
+    let other = Board::new(2, vec![0, 1, 2, 2]);
+    write_words(&mut buf, PackedBoard::new(&other, bits).words()).unwrap();
+    let packed = Reader { goal: &goal, bits };
+    assert!(matches!(
+        packed.read_board(&mut &buf[..]),
+        Err(CheckpointError::InvalidFormat)
+    ));
+}
+
+#[test]
+fn test_checkpoint_heuristic() {
+    use crate::{Manhattan, PatternDatabase};
+
+    let goal = Board::new(3, vec![0, 1, 1, 2, 2, 2, 3, 3, 3]);
+    let mut board = goal.clone();
+    board.shuffle(30, &mut rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7));
+    board.path.clear();
+    let database = || Box::new(PatternDatabase::build(&goal, &[vec![1]]));
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.set_heuristic(database());
+    solver.mode = SearchMode::Bidirectional;
+    solver.open_node_limit = 5;
+    assert!(!solver.search());
+    let mut buf = vec![];
+    solver.write_checkpoint(&mut buf).unwrap();
+
+    // The database looked up by name has other groups.
+    assert!(matches!(
+        Solver::<rand_pcg::Pcg32>::read_checkpoint(&buf[..]),
+        Err(CheckpointError::HeuristicMismatch(_))
+    ));
+    assert!(matches!(
+        Solver::<rand_pcg::Pcg32>::read_checkpoint_with(&buf[..], Box::new(Manhattan::default())),
+        Err(CheckpointError::HeuristicMismatch(_))
+    ));
+    let mut loaded = Solver::<rand_pcg::Pcg32>::read_checkpoint_with(&buf[..], database()).unwrap();
+    loaded.open_node_limit = usize::MAX;
+    assert!(loaded.search());
+    crate::verify(&board, &goal, &loaded.result.unwrap().moves()).unwrap();
+}
@@ -1,4 +1,200 @@
-use crate::Board;
+use std::sync::OnceLock;
+
+use crate::{Board, Heuristic};
+
+/// Counts the goal tiles already in place from the board edges inward.
+pub struct Distance1;
+
+/// Sums the squared distance of every tile to its nearest goal cell of the
+/// same colour.
+#[derive(Default)]
+pub struct Distance2 {
+    nearest: Vec<Vec<isize>>,
+}
+
+/// Like `Distance2`, but each goal cell is only matched once, in scan order.
+pub struct Distance3;
+
+/// Rewards tiles in place near the edges and penalizes the others by their
+/// squared distance to the nearest unfilled goal cell of their colour.
+pub struct Distance4;
+
+/// Sums the distance of every tile to its nearest goal cell of the same
+/// colour. Every move shifts one tile by one cell, so this never
+/// overestimates.
+#[derive(Default)]
+pub struct Manhattan {
+    /// Built on first use if `prepare` was not called.
+    nearest: OnceLock<Vec<Vec<isize>>>,
+}
+
+/// For every colour, the distance from each cell to the nearest goal cell of
+/// that colour.
+pub fn nearest_goal_distances(goal: &Board) -> Vec<Vec<isize>> {
+    let mut nearest = vec![vec![]; 256];
+    for c in goal.cells.iter().filter(|c| **c != 0) {
+        if !nearest[*c as usize].is_empty() {
+            continue;
+        }
+        nearest[*c as usize] = (0..goal.cells.len())
+            .map(|i| {
+                (0..goal.cells.len())
+                    .filter(|j| goal.cells[*j] == *c)
+                    .map(|j| goal.index_distance(i, j))
+                    .min()
+                    .unwrap()
+            })
+            .collect();
+    }
+    nearest
+}
+
+impl Heuristic for Distance1 {
+    fn name(&self) -> &'static str {
+        "distance1"
+    }
+
+    fn for_goal(&self, _goal: &Board) -> Box<dyn Heuristic> {
+        Box::new(Distance1)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        compute_distance1(board, goal)
+    }
+}
+
+impl Heuristic for Distance2 {
+    fn name(&self) -> &'static str {
+        "distance2"
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        self.nearest = nearest_goal_distances(goal);
+    }
+
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let mut heuristic = Distance2::default();
+        heuristic.prepare(goal);
+        Box::new(heuristic)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        compute_distance2(board, goal)
+    }
+
+    fn update(
+        &self,
+        board: &Board,
+        _goal: &Board,
+        previous: isize,
+        from: usize,
+        to: usize,
+    ) -> Option<isize> {
+        let nearest = self.nearest.get(board.cells[to] as usize)?;
+        let (before, after) = (nearest.get(from)?, nearest.get(to)?);
+        Some(previous - before * before + after * after)
+    }
+}
+
+impl Heuristic for Distance3 {
+    fn name(&self) -> &'static str {
+        "distance3"
+    }
+
+    fn for_goal(&self, _goal: &Board) -> Box<dyn Heuristic> {
+        Box::new(Distance3)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        compute_distance3(board, goal)
+    }
+}
+
+impl Heuristic for Distance4 {
+    fn name(&self) -> &'static str {
+        "distance4"
+    }
+
+    fn for_goal(&self, _goal: &Board) -> Box<dyn Heuristic> {
+        Box::new(Distance4)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        compute_distance4(board, goal)
+    }
+
+    fn update(
+        &self,
+        board: &Board,
+        goal: &Board,
+        previous: isize,
+        from: usize,
+        to: usize,
+    ) -> Option<isize> {
+        let tile = board.cells[to];
+        if goal.cells[from] != tile && goal.cells[to] != tile {
+            // The tile is out of place before and after, so no tile comes
+            // into or out of place and only its own distance changes.
+            let (mut before, mut after) = (isize::MAX, isize::MAX);
+            for j in 0..goal.cells.len() {
+                if goal.cells[j] == tile && board.cells[j] != tile {
+                    before = before.min(board.index_distance(from, j));
+                    after = after.min(board.index_distance(to, j));
+                }
+            }
+            return Some(previous - before * before + after * after);
+        }
+        let mut before = board.cells.to_vec();
+        before.swap(from, to);
+        Some(
+            previous - distance4_part(board.width, &before, &goal.cells, tile)
+                + distance4_part(board.width, &board.cells, &goal.cells, tile),
+        )
+    }
+}
+
+impl Heuristic for Manhattan {
+    fn name(&self) -> &'static str {
+        "manhattan"
+    }
+
+    fn is_admissible(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        self.nearest = OnceLock::from(nearest_goal_distances(goal));
+    }
+
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let mut heuristic = Manhattan::default();
+        heuristic.prepare(goal);
+        Box::new(heuristic)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        let nearest = self.nearest.get_or_init(|| nearest_goal_distances(goal));
+        board
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != 0)
+            .map(|(i, c)| nearest[*c as usize][i])
+            .sum()
+    }
+
+    fn update(
+        &self,
+        board: &Board,
+        _goal: &Board,
+        previous: isize,
+        from: usize,
+        to: usize,
+    ) -> Option<isize> {
+        let nearest = self.nearest.get()?.get(board.cells[to] as usize)?;
+        Some(previous - nearest.get(from)? + nearest.get(to)?)
+    }
+}
 
 pub fn compute_distance1(board: &Board, goal: &Board) -> isize {
     let width = board.width;
@@ -130,7 +326,7 @@ pub fn compute_distance2(board: &Board, goal: &Board) -> isize {
                 d = d.min(board.index_distance(i, j));
             }
         }
-        distance += d * d;// * board.index_distance(i, goal.empty_cell);
+        distance += d * d; // * board.index_distance(i, goal.empty_cell);
     }
     distance
 }
@@ -152,7 +348,7 @@ pub fn compute_distance3(board: &Board, goal: &Board) -> isize {
                 jj = j;
             }
         }
-        distance += d * d;// * board.index_distance(i, goal.empty_cell);
+        distance += d * d; // * board.index_distance(i, goal.empty_cell);
         used[jj] = true;
     }
     distance
@@ -161,11 +357,38 @@ pub fn compute_distance3(board: &Board, goal: &Board) -> isize {
 pub fn compute_distance4(board: &Board, goal: &Board) -> isize {
     let width = board.width;
     let height = board.cells.len() / board.width;
+    let ss = edge_scores(width, &board.cells, &goal.cells);
+
+    let mut distance = 0;
+    for i in 0..width * height {
+        if board.cells[i] == 0 {
+            continue;
+        }
+        if board.cells[i] == goal.cells[i] {
+            distance -= ss[i] * ss[i]; // + board.index_distance(i, goal.empty_cell);
+            continue;
+        }
+        let mut d = isize::MAX;
+        for j in 0..width * height {
+            if board.cells[j] != goal.cells[j] && board.cells[i] == goal.cells[j] {
+                d = d.min(board.index_distance(i, j));
+            }
+        }
+        distance += d * d;
+    }
+
+    distance // - board.index_distance(board.empty_cell, goal.empty_cell) * 2
+}
+
+/// For every cell, one more than the number of edge scans of
+/// `compute_distance4` reaching it through tiles already in place.
+fn edge_scores(width: usize, cells: &[u8], goal: &[u8]) -> Vec<isize> {
+    let height = cells.len() / width;
     let f = |x: usize, y: usize| {
         let p = x + y * width;
-        goal.cells[p] != 0 && board.cells[p] == goal.cells[p]
+        goal[p] != 0 && cells[p] == goal[p]
     };
-    let mut ss = vec![1; board.cells.len()];
+    let mut ss = vec![1; cells.len()];
     {
         let mut max_y = height;
         for x in 0..width {
@@ -271,27 +494,30 @@ pub fn compute_distance4(board: &Board, goal: &Board) -> isize {
         }
     }
 
-    // dbg!(&ss);
+    ss
+}
 
-    let mut distance = 0;
-    for i in 0..width * height {
-        if board.cells[i] == 0 {
-            continue;
-        }
-        if board.cells[i] == goal.cells[i] {
-            distance -= ss[i] * ss[i];// + board.index_distance(i, goal.empty_cell);
-            continue;
-        }
-        let mut d = isize::MAX;
-        for j in 0..width * height {
-            if board.cells[j] != goal.cells[j] && board.cells[i] == goal.cells[j] {
-                d = d.min(board.index_distance(i, j));
-            }
-        }
+/// The part of `compute_distance4` that changes when a tile of `color` comes
+/// into or out of place: the tiles in place, which depend on the whole
+/// board, and the misplaced tiles of `color`.
+fn distance4_part(width: usize, cells: &[u8], goal: &[u8], color: u8) -> isize {
+    let ss = edge_scores(width, cells, goal);
+    let mut distance = (0..cells.len())
+        .filter(|i| cells[*i] != 0 && cells[*i] == goal[*i])
+        .map(|i| -ss[i] * ss[i])
+        .sum();
+    let index_distance = |i: usize, j: usize| {
+        (i as isize % width as isize - j as isize % width as isize).abs()
+            + (i as isize / width as isize - j as isize / width as isize).abs()
+    };
+    let open: Vec<usize> = (0..goal.len())
+        .filter(|j| goal[*j] == color && cells[*j] != color)
+        .collect();
+    for i in (0..cells.len()).filter(|i| cells[*i] == color && goal[*i] != color) {
+        let d = open.iter().map(|j| index_distance(i, *j)).min().unwrap();
         distance += d * d;
     }
-
-    distance// - board.index_distance(board.empty_cell, goal.empty_cell) * 2
+    distance
 }
 
 #[test]
@@ -321,3 +547,18 @@ fn test_distance() {
     // board.path = vec![];
     // dbg!(board.check_mate(&initial_board, 10));
 }
+
+#[test]
+fn test_distance4_update() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    let mut value = Distance4.evaluate(&board, &goal);
+    for _ in 0..500 {
+        let from = board.empty_cell;
+        board.shuffle(1, &mut rng);
+        let to = board.empty_cell;
+        value = Distance4.update(&board, &goal, value, to, from).unwrap();
+        assert_eq!(value, compute_distance4(&board, &goal));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::{format_color, parse_color, Board, BoardError, Palette, PaletteError};
+
+/// A puzzle stored in the `.pa` format.
+///
+/// A `.pa` file holds two character grids separated by a blank line: the
+/// start board followed by the goal board. `#` is the empty cell, `_` and `.`
+/// are the colours 1 and 2, and the digits `0`-`9` are the colours of the
+/// same value (`0` being the empty cell).
+///
+/// The grids may be preceded by a header of `key: value` lines and `//`
+/// comments:
+///
+/// ```text
+/// // drawn for the 2021 calendar
+/// title: Carrot
+/// author: carrotflakes
+/// moves: 1262
+/// color: 1 #ffffff
+/// color: r #ff8000
+/// ```
+///
+/// `color` declares a palette entry; symbols other than the built-in ones
+/// become usable in the grids once declared.
+#[derive(Clone)]
+pub struct PuzzleFile {
+    pub header: Header,
+    pub palette: Palette,
+    pub start: Board,
+    pub goal: Board,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Target number of moves for a solution.
+    pub moves: Option<usize>,
+    /// Comment lines without the leading `//`.
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number.
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownKey(String),
+    InvalidValue(String),
+    Palette(PaletteError),
+    MissingStart,
+    MissingGoal,
+    TrailingContent,
+    UnknownSymbol(char),
+    RaggedRow { expected: usize, found: usize },
+    SizeMismatch,
+    Board(BoardError),
+}
+
+pub fn parse(s: &str) -> Result<PuzzleFile, ParseError> {
+    let lines: Vec<&str> = s.lines().map(|l| l.trim_end()).collect();
+    let mut header = Header::default();
+    let mut palette = Palette::new();
+    let mut pos = 0;
+
+    parse_header(&lines, &mut pos, &mut header, &mut palette)?;
+    let (_, start) = parse_grid(&lines, &mut pos, &mut palette, ParseErrorKind::MissingStart)?;
+    let (goal_line, goal) =
+        parse_grid(&lines, &mut pos, &mut palette, ParseErrorKind::MissingGoal)?;
+    if let Some(i) = (pos..lines.len()).find(|i| !lines[*i].is_empty()) {
+        return Err(ParseError {
+            line: i + 1,
+            column: 1,
+            kind: ParseErrorKind::TrailingContent,
+        });
+    }
+    if start.width != goal.width || start.cells.len() != goal.cells.len() {
+        return Err(ParseError {
+            line: goal_line,
+            column: 1,
+            kind: ParseErrorKind::SizeMismatch,
+        });
+    }
+
+    Ok(PuzzleFile {
+        header,
+        palette,
+        start,
+        goal,
+    })
+}
+
+fn is_header_line(l: &str) -> bool {
+    l.starts_with("//") || l.contains(':')
+}
+
+fn parse_header(
+    lines: &[&str],
+    pos: &mut usize,
+    header: &mut Header,
+    palette: &mut Palette,
+) -> Result<(), ParseError> {
+    while *pos < lines.len() && (lines[*pos].is_empty() || is_header_line(lines[*pos])) {
+        let l = lines[*pos];
+        let line = *pos + 1;
+        *pos += 1;
+        if l.is_empty() {
+            continue;
+        }
+        if let Some(comment) = l.strip_prefix("//") {
+            header.comments.push(comment.to_string());
+            continue;
+        }
+
+        let (key, value) = l.split_once(':').unwrap();
+        let column = key.len() + 2 + (value.len() - value.trim_start().len());
+        let error = |kind| ParseError { line, column, kind };
+        let invalid = || error(ParseErrorKind::InvalidValue(value.trim().to_string()));
+        let value = value.trim();
+        match key.trim() {
+            "title" => header.title = Some(value.to_string()),
+            "author" => header.author = Some(value.to_string()),
+            "moves" => header.moves = Some(value.parse().map_err(|_| invalid())?),
+            "color" => {
+                let mut words = value.split_whitespace();
+                let mut symbol = words.next().ok_or_else(invalid)?.chars();
+                let color = words.next().and_then(parse_color).ok_or_else(invalid)?;
+                match (symbol.next(), symbol.next(), words.next()) {
+                    (Some(symbol), None, None) => {
+                        palette
+                            .declare(symbol, Some(color))
+                            .map_err(|e| error(ParseErrorKind::Palette(e)))?;
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            key => {
+                return Err(ParseError {
+                    line,
+                    column: 1,
+                    kind: ParseErrorKind::UnknownKey(key.to_string()),
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses the grid starting at the first non-blank line from `*pos` and
+/// leaves `*pos` on the line following it.
+fn parse_grid(
+    lines: &[&str],
+    pos: &mut usize,
+    palette: &mut Palette,
+    missing: ParseErrorKind,
+) -> Result<(usize, Board), ParseError> {
+    while *pos < lines.len() && lines[*pos].is_empty() {
+        *pos += 1;
+    }
+    if *pos == lines.len() {
+        return Err(ParseError {
+            line: lines.len().max(1),
+            column: 1,
+            kind: missing,
+        });
+    }
+    let first_line = *pos + 1;
+
+    let width = lines[*pos].chars().count();
+    let mut cells = vec![];
+    while *pos < lines.len() && !lines[*pos].is_empty() {
+        let line = *pos + 1;
+        let mut row_width = 0;
+        for (column, c) in lines[*pos].chars().enumerate() {
+            let value = palette.resolve(c).map_err(|e| ParseError {
+                line,
+                column: column + 1,
+                kind: match e {
+                    PaletteError::UnknownSymbol(c) => ParseErrorKind::UnknownSymbol(c),
+                    e => ParseErrorKind::Palette(e),
+                },
+            })?;
+            cells.push(value);
+            row_width += 1;
+        }
+        if row_width != width {
+            return Err(ParseError {
+                line,
+                column: row_width.min(width) + 1,
+                kind: ParseErrorKind::RaggedRow {
+                    expected: width,
+                    found: row_width,
+                },
+            });
+        }
+        *pos += 1;
+    }
+
+    let board = Board::try_new(width, cells).map_err(|e| ParseError {
+        line: first_line,
+        column: 1,
+        kind: ParseErrorKind::Board(e),
+    })?;
+    Ok((first_line, board))
+}
+
+impl PuzzleFile {
+    pub fn new(start: Board, goal: Board) -> Self {
+        Self {
+            header: Header::default(),
+            palette: Palette::new(),
+            start,
+            goal,
+        }
+    }
+
+    /// Returns a solver for the puzzle, carrying its header and palette.
+    pub fn solver(&self) -> Result<crate::Solver<rand_pcg::Lcg64Xsh32>, BoardError> {
+        let mut solver = crate::Solver::try_new(self.goal.clone(), self.start.clone())?;
+        solver.header = self.header.clone();
+        solver.palette = self.palette.clone();
+        Ok(solver)
+    }
+
+    fn write_header(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for comment in &self.header.comments {
+            writeln!(f, "//{}", comment)?;
+        }
+        if let Some(title) = &self.header.title {
+            writeln!(f, "title: {}", title)?;
+        }
+        if let Some(author) = &self.header.author {
+            writeln!(f, "author: {}", author)?;
+        }
+        if let Some(moves) = self.header.moves {
+            writeln!(f, "moves: {}", moves)?;
+        }
+        for entry in &self.palette.entries {
+            if let Some(color) = entry.color {
+                writeln!(f, "color: {} {}", entry.symbol, format_color(color))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_board(&self, f: &mut std::fmt::Formatter, board: &Board) -> std::fmt::Result {
+        for row in board.cells.chunks(board.width) {
+            for c in row {
+                write!(f, "{}", self.palette.symbol(*c).unwrap_or('?'))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PuzzleFile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+impl std::fmt::Display for PuzzleFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.write_header(f)?;
+        if self.header != Header::default()
+            || self.palette.entries.iter().any(|e| e.color.is_some())
+        {
+            writeln!(f)?;
+        }
+        self.write_board(f, &self.start)?;
+        writeln!(f)?;
+        self.write_board(f, &self.goal)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::UnknownKey(key) => write!(f, "unknown header key {:?}", key),
+            ParseErrorKind::InvalidValue(value) => write!(f, "invalid value {:?}", value),
+            ParseErrorKind::Palette(e) => write!(f, "{}", e),
+            ParseErrorKind::MissingStart => write!(f, "missing start board"),
+            ParseErrorKind::MissingGoal => write!(f, "missing goal board"),
+            ParseErrorKind::TrailingContent => write!(f, "unexpected content after goal board"),
+            ParseErrorKind::UnknownSymbol(c) => write!(f, "unknown symbol {:?}", c),
+            ParseErrorKind::RaggedRow { expected, found } => {
+                write!(f, "row has {} cells, expected {}", found, expected)
+            }
+            ParseErrorKind::SizeMismatch => write!(f, "goal board size differs from start board"),
+            ParseErrorKind::Board(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[test]
+fn test_parse() {
+    for s in [
+        include_str!("../min.pa"),
+        include_str!("../smile.pa"),
+        include_str!("../carrot.pa"),
+        include_str!("../hello.pa"),
+    ] {
+        let file = parse(s).unwrap();
+        assert_eq!(file.to_string(), s);
+    }
+
+    let s = "// two colours\ntitle: Tiny\nmoves: 3\ncolor: _ #ffffff\ncolor: r #ff0000\n\n#_\nr_\n\n_#\n_r\n";
+    let file = parse(s).unwrap();
+    assert_eq!(file.to_string(), s);
+    assert_eq!(file.header.title.as_deref(), Some("Tiny"));
+    assert_eq!(file.header.moves, Some(3));
+    assert_eq!(file.start.cells, vec![0, 1, 10, 1]);
+    assert_eq!(file.palette.color(10, 10), [255, 0, 0]);
+
+    let file = parse("#_\n._\n\n_#\n_.\n").unwrap();
+    assert_eq!(file.start.cells, vec![0, 1, 2, 1]);
+    assert_eq!(file.goal.empty_cell, 1);
+
+    let err = |s| parse(s).err().map(|e| (e.line, e.column, e.kind));
+    assert_eq!(err("#_\n._\n"), Some((2, 1, ParseErrorKind::MissingGoal)));
+    assert_eq!(
+        err("#_\n.x\n\n_#\n_.\n"),
+        Some((2, 2, ParseErrorKind::UnknownSymbol('x')))
+    );
+    assert_eq!(
+        err("#_\n._\n\n_#\n_._\n"),
+        Some((
+            5,
+            3,
+            ParseErrorKind::RaggedRow {
+                expected: 2,
+                found: 3
+            }
+        ))
+    );
+    assert_eq!(
+        err("moves: many\n\n#_\n._\n\n_#\n_.\n"),
+        Some((1, 8, ParseErrorKind::InvalidValue("many".to_string())))
+    );
+    assert_eq!(
+        err("#_\n._\n\n__\n_.\n"),
+        Some((4, 1, ParseErrorKind::Board(BoardError::NoEmptyCell)))
+    );
+    assert_eq!(
+        err("color: 1 #ffffff\n\n#_\n.1\n\n1#\n_.\n"),
+        Some((
+            3,
+            2,
+            ParseErrorKind::Palette(PaletteError::SharedValue {
+                symbol: '_',
+                other: '1'
+            })
+        ))
+    );
+}
@@ -0,0 +1,172 @@
+use crate::{Board, BoardError, Palette};
+
+/// How `generate_start` lays out the goal's tiles.
+#[derive(Clone)]
+pub enum StartStyle {
+    /// Colours sorted into horizontal bands in order of value.
+    Bands,
+    /// The most common colour fills the board and the remaining tiles form a
+    /// strip along the bottom, like a blank canvas.
+    Canvas,
+    /// Approximates another picture of the same size, each cell taking the
+    /// remaining tile whose colour is closest.
+    Image { board: Board, palette: Palette },
+}
+
+/// Produces a start board holding exactly the tiles of `goal`, with the
+/// empty cell in the same place and reachable from the goal.
+///
+/// Fails with `SizeMismatch` if the picture of `StartStyle::Image` differs
+/// in shape from `goal`, and with `Unreachable` if the layout cannot be
+/// reached, as on boards one cell wide or high where tiles never pass each
+/// other.
+pub fn generate_start(
+    goal: &Board,
+    palette: &Palette,
+    style: &StartStyle,
+) -> Result<Board, BoardError> {
+    let mut counts = [0usize; 256];
+    for c in &goal.cells {
+        counts[*c as usize] += 1;
+    }
+    counts[0] = 0;
+
+    let tiles: Vec<u8> = match style {
+        StartStyle::Bands => sorted_tiles(&counts, None),
+        StartStyle::Canvas => {
+            let dominant = (1..256)
+                .max_by_key(|c| (counts[*c], usize::MAX - c))
+                .unwrap();
+            sorted_tiles(&counts, Some(dominant as u8))
+        }
+        StartStyle::Image {
+            board,
+            palette: image_palette,
+        } => {
+            if board.width != goal.width || board.cells.len() != goal.cells.len() {
+                return Err(BoardError::SizeMismatch);
+            }
+            return fix_parity(
+                goal,
+                picture_cells(goal, palette, &counts, board, image_palette),
+            );
+        }
+    };
+
+    let mut tiles = tiles.into_iter();
+    let cells = (0..goal.cells.len())
+        .map(|i| {
+            if i == goal.empty_cell {
+                0
+            } else {
+                tiles.next().unwrap()
+            }
+        })
+        .collect();
+    fix_parity(goal, cells)
+}
+
+fn sorted_tiles(counts: &[usize; 256], first: Option<u8>) -> Vec<u8> {
+    let mut tiles = vec![];
+    if let Some(first) = first {
+        tiles.extend(std::iter::repeat_n(first, counts[first as usize]));
+    }
+    for c in 1..=255u8 {
+        if Some(c) != first {
+            tiles.extend(std::iter::repeat_n(c, counts[c as usize]));
+        }
+    }
+    tiles
+}
+
+fn picture_cells(
+    goal: &Board,
+    palette: &Palette,
+    counts: &[usize; 256],
+    picture: &Board,
+    picture_palette: &Palette,
+) -> Vec<u8> {
+    let max_value = goal.cells.iter().cloned().max().unwrap_or(0);
+    let picture_max = picture.cells.iter().cloned().max().unwrap_or(0);
+    let mut counts = *counts;
+    let mut cells = vec![0; goal.cells.len()];
+    let wanted = |i: usize| picture_palette.color(picture.cells[i], picture_max);
+
+    // Cells whose wanted colour is available as is come first, so that the
+    // closest-colour pass only has to fill in what is left over.
+    let mut rest = vec![];
+    for (i, cell) in cells.iter_mut().enumerate() {
+        if i == goal.empty_cell {
+            continue;
+        }
+        let color = wanted(i);
+        match (1..256).find(|c| counts[*c] > 0 && palette.color(*c as u8, max_value) == color) {
+            Some(c) => {
+                counts[c] -= 1;
+                *cell = c as u8;
+            }
+            None => rest.push(i),
+        }
+    }
+    for i in rest {
+        let color = wanted(i);
+        let distance = |c: usize| {
+            let p = palette.color(c as u8, max_value);
+            (0..3)
+                .map(|k| (p[k] as isize - color[k] as isize).pow(2))
+                .sum::<isize>()
+        };
+        let c = (1..256)
+            .filter(|c| counts[*c] > 0)
+            .min_by_key(|c| distance(*c))
+            .unwrap();
+        counts[c] -= 1;
+        cells[i] = c as u8;
+    }
+    cells
+}
+
+/// Swaps two tiles of different colours if the start would otherwise be
+/// unreachable, which can only happen when all tiles are distinct. A swap
+/// cannot help on boards one cell wide or high, which stay unreachable.
+fn fix_parity(goal: &Board, cells: Vec<u8>) -> Result<Board, BoardError> {
+    let mut board = Board::new(goal.width, cells);
+    if !board.is_reachable_from(goal) {
+        let tiles: Vec<usize> = (0..board.cells.len())
+            .filter(|i| *i != board.empty_cell)
+            .collect();
+        if let [.., a, b] = tiles[..] {
+            board.swap_cells(a, b);
+        }
+    }
+    if !board.is_reachable_from(goal) {
+        return Err(BoardError::Unreachable);
+    }
+    Ok(board)
+}
+
+#[test]
+fn test_generate_start() {
+    let file = crate::format::parse(include_str!("../carrot.pa")).unwrap();
+    let start = generate_start(&file.goal, &file.palette, &StartStyle::Canvas).unwrap();
+    assert_eq!(start.cells, file.start.cells);
+
+    let goal = Board::new(2, vec![0, 3, 2, 1]);
+    let start = generate_start(&goal, &Palette::new(), &StartStyle::Bands).unwrap();
+    assert!(start.is_reachable_from(&goal));
+    assert_eq!(start.cells, vec![0, 1, 3, 2]);
+
+    let picture = StartStyle::Image {
+        board: Board::new(1, vec![0, 1]),
+        palette: Palette::new(),
+    };
+    assert_eq!(
+        generate_start(&goal, &Palette::new(), &picture).err(),
+        Some(BoardError::SizeMismatch)
+    );
+    let goal = Board::new(4, vec![0, 3, 2, 1]);
+    assert_eq!(
+        generate_start(&goal, &Palette::new(), &StartStyle::Bands).err(),
+        Some(BoardError::Unreachable)
+    );
+}
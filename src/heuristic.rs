@@ -0,0 +1,123 @@
+use crate::{
+    Assignment, Board, Distance1, Distance2, Distance3, Distance4, LinearConflict, Manhattan,
+    PatternDatabase, WalkingDistance,
+};
+
+/// Estimates how far a board is from the goal.
+pub trait Heuristic: Send + Sync {
+    /// Name used to select the heuristic, e.g. on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Whether the estimate never exceeds the number of moves left.
+    fn is_admissible(&self) -> bool {
+        false
+    }
+
+    /// Called with the goal before any evaluation, to precompute tables.
+    fn prepare(&mut self, _goal: &Board) {}
+
+    /// Returns a heuristic configured like this one and prepared for
+    /// `goal`, for searching toward another board.
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic>;
+
+    /// Identifies the tables the heuristic was prepared with, beyond what
+    /// follows from the goal, so that a saved search can tell whether it is
+    /// resumed with the same ones.
+    fn fingerprint(&self) -> u64 {
+        0
+    }
+
+    /// Estimates the moves from `board` to `goal`. Heuristics with tables
+    /// build them for `goal` on first use unless `prepare` was called.
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize;
+
+    /// Returns the value of `board` right after `Board::move_to` slid the tile
+    /// at `from` into `to`, given the value `previous` before the move.
+    /// `None` means the heuristic has to be evaluated from scratch.
+    fn update(
+        &self,
+        _board: &Board,
+        _goal: &Board,
+        _previous: isize,
+        _from: usize,
+        _to: usize,
+    ) -> Option<isize> {
+        None
+    }
+}
+
+/// Returns one instance of every built-in heuristic.
+pub fn heuristics() -> Vec<Box<dyn Heuristic>> {
+    vec![
+        Box::new(Distance1),
+        Box::new(Distance2::default()),
+        Box::new(Distance3),
+        Box::new(Distance4),
+        Box::new(Manhattan::default()),
+        Box::new(Assignment::default()),
+        Box::new(LinearConflict::default()),
+        Box::new(WalkingDistance::default()),
+        Box::new(PatternDatabase::default()),
+    ]
+}
+
+pub fn heuristic_by_name(name: &str) -> Option<Box<dyn Heuristic>> {
+    heuristics().into_iter().find(|h| h.name() == name)
+}
+
+#[test]
+fn test_heuristic_by_name() {
+    let names: Vec<&str> = heuristics().iter().map(|h| h.name()).collect();
+    for name in &names {
+        assert_eq!(heuristic_by_name(name).unwrap().name(), *name);
+    }
+    assert_eq!(names.len(), {
+        let mut unique = names.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        unique.len()
+    });
+    assert!(heuristic_by_name("none").is_none());
+}
+
+#[test]
+fn test_update() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    for mut heuristic in heuristics() {
+        if heuristic.name() == "pattern_database" {
+            // The default limit makes the tables for this goal slow to build.
+            heuristic = Box::new(PatternDatabase::with_state_limit(1 << 15));
+        }
+        // Tables are built on first use without `prepare`.
+        let mut shuffled = goal.clone();
+        shuffled.shuffle(20, &mut rng);
+        let unprepared = heuristic.evaluate(&shuffled, &goal);
+        heuristic.prepare(&goal);
+        assert_eq!(
+            heuristic.evaluate(&shuffled, &goal),
+            unprepared,
+            "{}",
+            heuristic.name()
+        );
+        let mut board = goal.clone();
+        let mut value = heuristic.evaluate(&board, &goal);
+        for _ in 0..100 {
+            let from = board.empty_cell;
+            board.shuffle(1, &mut rng);
+            let to = board.empty_cell;
+            // The tile moved from the new empty cell into the old one.
+            if let Some(updated) = heuristic.update(&board, &goal, value, to, from) {
+                value = updated;
+                assert_eq!(
+                    value,
+                    heuristic.evaluate(&board, &goal),
+                    "{}",
+                    heuristic.name()
+                );
+            } else {
+                value = heuristic.evaluate(&board, &goal);
+            }
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use std::io::Read;
+
+use crate::{render::Image, Board, Palette, Rgb};
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Png(png::DecodingError),
+    InvalidPpm(&'static str),
+    EmptyImage,
+    UnsupportedFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantizer {
+    MedianCut,
+    /// k-means seeded with the median cut colours.
+    KMeans {
+        iterations: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    /// 4x4 Bayer matrix.
+    Ordered,
+    FloydSteinberg,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Number of cells per row; the height follows the image's aspect ratio.
+    pub width: usize,
+    /// Number of tile colours, not counting the empty cell.
+    pub colors: usize,
+    pub quantizer: Quantizer,
+    pub dither: Dither,
+    /// Index of the cell left empty.
+    pub empty_cell: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            width: 16,
+            colors: 4,
+            quantizer: Quantizer::MedianCut,
+            dither: Dither::None,
+            empty_cell: 0,
+        }
+    }
+}
+
+/// Symbols given to the imported colours, in order.
+const SYMBOLS: &str = "123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub fn load_image(path: impl AsRef<std::path::Path>) -> Result<Image, ImportError> {
+    let data = std::fs::read(path).map_err(ImportError::Io)?;
+    if data.starts_with(b"\x89PNG") {
+        read_png(&data[..])
+    } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+        read_ppm(&data)
+    } else {
+        Err(ImportError::UnsupportedFormat)
+    }
+}
+
+/// Decodes a PNG image; transparent pixels are blended onto white.
+pub fn read_png(reader: impl Read) -> Result<Image, ImportError> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(ImportError::Png)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(ImportError::Png)?;
+    if info.width == 0 || info.height == 0 {
+        return Err(ImportError::EmptyImage);
+    }
+    let channels = info.color_type.samples();
+    let blend = |c: u8, a: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+    let pixels = buf[..info.buffer_size()]
+        .chunks(channels)
+        .map(|p| match p {
+            [l] => [*l, *l, *l],
+            [l, a] => [blend(*l, *a); 3],
+            [r, g, b] => [*r, *g, *b],
+            [r, g, b, a] => [blend(*r, *a), blend(*g, *a), blend(*b, *a)],
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+/// Decodes a plain (`P3`) or raw (`P6`) PPM image.
+pub fn read_ppm(data: &[u8]) -> Result<Image, ImportError> {
+    let mut pos = 2;
+    let number = |pos: &mut usize, what| {
+        next_token(data, pos)
+            .and_then(|t| std::str::from_utf8(t).ok())
+            .and_then(|t| t.parse::<usize>().ok())
+            .ok_or(ImportError::InvalidPpm(what))
+    };
+
+    let raw = match data.get(..2) {
+        Some(b"P3") => false,
+        Some(b"P6") => true,
+        _ => return Err(ImportError::UnsupportedFormat),
+    };
+    let width = number(&mut pos, "width")?;
+    let height = number(&mut pos, "height")?;
+    if width == 0 || height == 0 {
+        return Err(ImportError::EmptyImage);
+    }
+    let len = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or(ImportError::InvalidPpm("size"))?;
+    let max_value = number(&mut pos, "max value")?;
+    if max_value == 0 || max_value > 255 {
+        return Err(ImportError::InvalidPpm("max value"));
+    }
+    let scale = |v: usize| (v.min(max_value) * 255 / max_value) as u8;
+
+    // Every sample takes at least one byte, so `data` bounds the capacity
+    // whatever the header claims.
+    let mut samples = Vec::with_capacity(len.min(data.len()));
+    if raw {
+        let bytes = data
+            .get(pos + 1..)
+            .and_then(|d| d.get(..len))
+            .ok_or(ImportError::InvalidPpm("pixel data"))?;
+        samples.extend(bytes.iter().map(|b| scale(*b as usize)));
+    } else {
+        for _ in 0..len {
+            samples.push(scale(number(&mut pos, "pixel data")?));
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels: samples.chunks(3).map(|p| [p[0], p[1], p[2]]).collect(),
+    })
+}
+
+/// Returns the next whitespace-separated token, skipping `#` comments.
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < data.len() && data[*pos] == b'#' {
+            while *pos < data.len() && data[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    (start < *pos).then(|| &data[start..*pos])
+}
+
+/// Shrinks `image` to `width` columns by averaging the covered pixels. An
+/// empty image stays empty.
+pub fn downscale(image: &Image, width: usize) -> Image {
+    if image.width == 0 || image.height == 0 {
+        return Image::new(0, 0);
+    }
+    let width = width.clamp(1, image.width.max(1));
+    let height = ((image.height * width + image.width / 2) / image.width.max(1)).max(1);
+    let mut result = Image::new(width, height);
+    for y in 0..height {
+        let (y0, y1) = span(y, height, image.height);
+        for x in 0..width {
+            let (x0, x1) = span(x, width, image.width);
+            let mut sum = [0usize; 3];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let p = image.get(sx, sy);
+                    for c in 0..3 {
+                        sum[c] += p[c] as usize;
+                    }
+                }
+            }
+            let n = (x1 - x0) * (y1 - y0);
+            result.set(x, y, sum.map(|s| ((s + n / 2) / n) as u8));
+        }
+    }
+    result
+}
+
+fn span(i: usize, n: usize, len: usize) -> (usize, usize) {
+    let start = i * len / n;
+    let end = ((i + 1) * len / n).max(start + 1).min(len);
+    (start, end)
+}
+
+/// Picks up to `k` colours representing `pixels`, darkest first.
+pub fn quantize(pixels: &[Rgb], k: usize, quantizer: Quantizer) -> Vec<Rgb> {
+    let mut colors = median_cut(pixels, k);
+    if let Quantizer::KMeans { iterations } = quantizer {
+        colors = k_means(pixels, colors, iterations);
+    }
+    // Colours of equal luminance need not be adjacent, so drop duplicates
+    // while they are still sorted by the colour itself.
+    colors.sort_unstable();
+    colors.dedup();
+    colors.sort_by_key(|c| luminance(*c));
+    colors
+}
+
+fn median_cut(pixels: &[Rgb], k: usize) -> Vec<Rgb> {
+    let mut boxes = vec![pixels.to_vec()];
+    while boxes.len() < k {
+        let (i, channel, range) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let (channel, range) = (0..3)
+                    .map(|c| {
+                        let min = b.iter().map(|p| p[c]).min().unwrap_or(0);
+                        let max = b.iter().map(|p| p[c]).max().unwrap_or(0);
+                        (c, max - min)
+                    })
+                    .max_by_key(|(_, range)| *range)
+                    .unwrap();
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range)
+            .unwrap();
+        if range == 0 {
+            break;
+        }
+        let mut b = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|p| p[channel]);
+        let upper = b.split_off(b.len() / 2);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| mean(b))
+        .collect()
+}
+
+fn k_means(pixels: &[Rgb], mut colors: Vec<Rgb>, iterations: usize) -> Vec<Rgb> {
+    for _ in 0..iterations {
+        let mut clusters = vec![vec![]; colors.len()];
+        for p in pixels {
+            clusters[nearest(&colors, [p[0] as f32, p[1] as f32, p[2] as f32])].push(*p);
+        }
+        let next: Vec<Rgb> = clusters
+            .iter()
+            .zip(&colors)
+            .map(|(c, color)| if c.is_empty() { *color } else { mean(c) })
+            .collect();
+        if next == colors {
+            break;
+        }
+        colors = next;
+    }
+    colors
+}
+
+fn mean(pixels: &[Rgb]) -> Rgb {
+    let mut sum = [0usize; 3];
+    for p in pixels {
+        for c in 0..3 {
+            sum[c] += p[c] as usize;
+        }
+    }
+    let n = pixels.len().max(1);
+    sum.map(|s| ((s + n / 2) / n) as u8)
+}
+
+fn luminance(c: Rgb) -> u32 {
+    c[0] as u32 * 299 + c[1] as u32 * 587 + c[2] as u32 * 114
+}
+
+fn nearest(colors: &[Rgb], p: [f32; 3]) -> usize {
+    let distance = |c: &Rgb| (0..3).map(|i| (c[i] as f32 - p[i]).powi(2)).sum::<f32>();
+    (0..colors.len())
+        .min_by(|a, b| distance(&colors[*a]).total_cmp(&distance(&colors[*b])))
+        .unwrap()
+}
+
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Maps every pixel of `image` to the index of a colour in `colors`.
+pub fn dither(image: &Image, colors: &[Rgb], dither: Dither) -> Vec<usize> {
+    let to_f32 = |p: Rgb| [p[0] as f32, p[1] as f32, p[2] as f32];
+    match dither {
+        Dither::None => image
+            .pixels
+            .iter()
+            .map(|p| nearest(colors, to_f32(*p)))
+            .collect(),
+        Dither::Ordered => {
+            let spread = 255.0 / colors.len().max(1) as f32;
+            (0..image.pixels.len())
+                .map(|i| {
+                    let (x, y) = (i % image.width, i / image.width);
+                    let offset = (BAYER[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                    nearest(colors, to_f32(image.pixels[i]).map(|c| c + offset * spread))
+                })
+                .collect()
+        }
+        Dither::FloydSteinberg => {
+            let mut buf: Vec<[f32; 3]> = image.pixels.iter().map(|p| to_f32(*p)).collect();
+            let mut indices = vec![0; buf.len()];
+            for y in 0..image.height {
+                for x in 0..image.width {
+                    let i = x + y * image.width;
+                    let index = nearest(colors, buf[i]);
+                    indices[i] = index;
+                    let error = [0, 1, 2].map(|c| buf[i][c] - colors[index][c] as f32);
+                    let mut spread = |dx: isize, dy: usize, weight: f32| {
+                        let nx = x as isize + dx;
+                        if nx < 0 || nx as usize >= image.width || y + dy >= image.height {
+                            return;
+                        }
+                        let j = nx as usize + (y + dy) * image.width;
+                        for c in 0..3 {
+                            buf[j][c] += error[c] * weight;
+                        }
+                    };
+                    spread(1, 0, 7.0 / 16.0);
+                    spread(-1, 1, 3.0 / 16.0);
+                    spread(0, 1, 5.0 / 16.0);
+                    spread(1, 1, 1.0 / 16.0);
+                }
+            }
+            indices
+        }
+    }
+}
+
+/// Turns `image` into a board of `options.width` columns using at most
+/// `options.colors` tile colours. The returned palette declares one symbol
+/// per colour, darkest first.
+pub fn image_to_board(
+    image: &Image,
+    options: &ImportOptions,
+) -> Result<(Board, Palette), ImportError> {
+    if image.width == 0 || image.height == 0 {
+        return Err(ImportError::EmptyImage);
+    }
+    let image = downscale(image, options.width);
+    let colors = quantize(
+        &image.pixels,
+        options.colors.clamp(1, SYMBOLS.len()),
+        options.quantizer,
+    );
+    let mut palette = Palette::new();
+    let values: Vec<u8> = colors
+        .iter()
+        .zip(SYMBOLS.chars())
+        .map(|(color, symbol)| palette.declare(symbol, Some(*color)).unwrap())
+        .collect();
+
+    let mut cells: Vec<u8> = dither(&image, &colors, options.dither)
+        .into_iter()
+        .map(|i| values[i])
+        .collect();
+    let empty_cell = options.empty_cell.min(cells.len() - 1);
+    cells[empty_cell] = 0;
+    Ok((Board::new(image.width, cells), palette))
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "{}", e),
+            ImportError::Png(e) => write!(f, "{}", e),
+            ImportError::InvalidPpm(what) => write!(f, "invalid PPM {}", what),
+            ImportError::EmptyImage => write!(f, "image has no pixels"),
+            ImportError::UnsupportedFormat => write!(f, "unsupported image format"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[test]
+fn test_image_to_board() {
+    let ppm = b"P3\n# 4x2, two colours\n4 2\n255\n\
+        255 0 0  255 0 0  0 0 255  0 0 255\n\
+        250 0 0  255 0 0  0 0 250  0 0 255\n";
+    let image = read_ppm(ppm).unwrap();
+    assert_eq!((image.width, image.height), (4, 2));
+
+    let (board, palette) = image_to_board(
+        &image,
+        &ImportOptions {
+            width: 2,
+            colors: 2,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(board.width, 2);
+    assert_eq!(board.cells, vec![0, 1]);
+    assert_eq!(palette.color(1, 2), [0, 0, 254]);
+    assert_eq!(palette.symbol(1), Some('1'));
+
+    assert!(matches!(
+        read_ppm(b"P3 0 0 255\n"),
+        Err(ImportError::EmptyImage)
+    ));
+    assert!(matches!(
+        read_ppm(b"P6 4294967296 4294967296 255\n"),
+        Err(ImportError::InvalidPpm(_))
+    ));
+    assert!(matches!(
+        image_to_board(&Image::new(0, 0), &ImportOptions::default()),
+        Err(ImportError::EmptyImage)
+    ));
+}
@@ -1,12 +1,38 @@
+mod assignment;
 mod board;
 mod check_mate;
+mod checkpoint;
 mod distance_fn;
+pub mod format;
+pub mod generate;
+mod heuristic;
+pub mod import;
+mod linear_conflict;
+mod moves;
+mod packed;
+mod palette;
+mod parallel;
+mod pattern_database;
+pub mod render;
 mod solver;
+mod verify;
+mod walking_distance;
 
+pub use assignment::*;
 pub use board::*;
 pub use check_mate::*;
+pub use checkpoint::*;
 pub use distance_fn::*;
+pub use heuristic::*;
+pub use linear_conflict::*;
+pub use moves::*;
+pub use packed::*;
+pub use palette::*;
+pub use parallel::*;
+pub use pattern_database::*;
 pub use solver::*;
+pub use verify::*;
+pub use walking_distance::*;
 
 pub fn print_path(path: &[usize]) {
     print!("len: {} ", path.len());
@@ -0,0 +1,159 @@
+use std::sync::OnceLock;
+
+use crate::{Board, Heuristic, Manhattan};
+
+/// `Manhattan` plus two moves for every tile that has to step out of its goal
+/// row or column to let another tile pass.
+///
+/// Only colours whose goal cells all lie in one row take part in row
+/// conflicts, since only their tiles are known to end up in that row, and
+/// likewise for columns. Two such tiles in a row conflict when every goal
+/// column of the left one lies right of every goal column of the other.
+#[derive(Default)]
+pub struct LinearConflict {
+    manhattan: Manhattan,
+    /// For every colour, its goal row and the range of its goal columns, if
+    /// all its goal cells lie in one row. Built on first use if `prepare`
+    /// was not called, like `column_spans`.
+    row_spans: OnceLock<Vec<Option<Span>>>,
+    /// The same for columns, with the range of goal rows.
+    column_spans: OnceLock<Vec<Option<Span>>>,
+}
+
+#[derive(Clone, Copy)]
+struct Span {
+    line: usize,
+    min: usize,
+    max: usize,
+}
+
+impl LinearConflict {
+    fn spans(goal: &Board, transpose: bool) -> Vec<Option<Span>> {
+        let mut spans: Vec<Option<Option<Span>>> = vec![None; 256];
+        for (i, c) in goal.cells.iter().enumerate().filter(|(_, c)| **c != 0) {
+            let (x, y) = goal.index_to_xy(i);
+            let (line, pos) = if transpose { (x, y) } else { (y, x) };
+            let span = &mut spans[*c as usize];
+            *span = match *span {
+                None => Some(Some(Span {
+                    line,
+                    min: pos,
+                    max: pos,
+                })),
+                Some(Some(s)) if s.line == line => Some(Some(Span {
+                    line,
+                    min: s.min.min(pos),
+                    max: s.max.max(pos),
+                })),
+                Some(_) => Some(None),
+            };
+        }
+        spans.into_iter().map(Option::flatten).collect()
+    }
+
+    /// Counts the tiles that must leave their goal line, over all rows or
+    /// all columns.
+    fn conflicts(&self, board: &Board, goal: &Board, transpose: bool) -> isize {
+        let spans = if transpose {
+            &self.column_spans
+        } else {
+            &self.row_spans
+        };
+        let spans = spans.get_or_init(|| Self::spans(goal, transpose));
+        let height = board.cells.len() / board.width;
+        let (lines, len) = if transpose {
+            (board.width, height)
+        } else {
+            (height, board.width)
+        };
+        let mut count = 0;
+        for line in 0..lines {
+            let tiles: Vec<Span> = (0..len)
+                .map(|pos| {
+                    if transpose {
+                        board.cells[line + pos * board.width]
+                    } else {
+                        board.cells[pos + line * board.width]
+                    }
+                })
+                .filter_map(|c| spans[c as usize].filter(|s| s.line == line))
+                .collect();
+            count += tiles.len() - max_conflict_free(&tiles, len);
+        }
+        count as isize
+    }
+}
+
+/// Returns the size of the largest subset of `tiles`, given in board order,
+/// in which no tile has to pass another. A subset qualifies when every
+/// tile's `max` is at least the `min` of each tile before it, so it is enough
+/// to track the largest `min` taken so far.
+fn max_conflict_free(tiles: &[Span], len: usize) -> usize {
+    // best[m]: largest subset so far whose largest `min` is `m`.
+    let mut best: Vec<Option<usize>> = vec![None; len];
+    best[0] = Some(0);
+    for tile in tiles {
+        for m in (0..len).rev() {
+            let Some(n) = best[m] else {
+                continue;
+            };
+            if m <= tile.max {
+                let next = m.max(tile.min);
+                best[next] = best[next].max(Some(n + 1));
+            }
+        }
+    }
+    best.into_iter().flatten().max().unwrap_or(0)
+}
+
+impl Heuristic for LinearConflict {
+    fn name(&self) -> &'static str {
+        "linear_conflict"
+    }
+
+    fn is_admissible(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        self.manhattan.prepare(goal);
+        self.row_spans = OnceLock::from(Self::spans(goal, false));
+        self.column_spans = OnceLock::from(Self::spans(goal, true));
+    }
+
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let mut heuristic = LinearConflict::default();
+        heuristic.prepare(goal);
+        Box::new(heuristic)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        self.manhattan.evaluate(board, goal)
+            + 2 * (self.conflicts(board, goal, false) + self.conflicts(board, goal, true))
+    }
+}
+
+#[test]
+fn test_linear_conflict() {
+    let mut heuristic = LinearConflict::default();
+    let goal = Board::new(3, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    heuristic.prepare(&goal);
+    assert_eq!(heuristic.evaluate(&goal, &goal), 0);
+    let board = Board::new(3, vec![3, 2, 1, 4, 5, 6, 7, 8, 0]);
+    // 1 and 3 are two columns off, and two of the three tiles have to leave
+    // the row.
+    assert_eq!(heuristic.evaluate(&board, &goal), 4 + 4);
+
+    // The 1s may pass each other freely, but the 2 has to get around them.
+    let goal = Board::new(3, vec![1, 1, 2, 3, 3, 3, 0, 3, 3]);
+    let board = Board::new(3, vec![2, 1, 1, 3, 3, 3, 0, 3, 3]);
+    heuristic.prepare(&goal);
+    assert_eq!(heuristic.evaluate(&board, &goal), 2 + 1 + 2);
+
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let mut board = goal.clone();
+    for _ in 0..100 {
+        board.shuffle(1, &mut rng);
+        assert!(heuristic.evaluate(&board, &goal) <= board.path.len() as isize);
+    }
+}
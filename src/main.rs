@@ -1,29 +1,614 @@
+use std::str::FromStr;
+
 use rand_pcg::Pcg32;
+use slidart::format::PuzzleFile;
+use slidart::render::{anim, gif};
+use slidart::{Board, SearchMode, Solver, Termination};
+
+const USAGE: &str = "usage: slidart <command> [args]
+
+commands:
+  solve <puzzle.pa>             search for a solution and print its moves
+      --seed N  --node-limit N  --heuristic NAME  --moves N
+      --output FILE  --gif FILE  --progress  --stats  --pdb FILE
+      --mode greedy|astar|ida-star|anytime|beam|bidirectional
+      --weight W  --beam-width N  --time-limit SECONDS  --threads N
+      --checkpoint FILE  --checkpoint-interval N  --resume FILE
+  shuffle <puzzle.pa>           replace the start board with a shuffled goal
+      --moves N  --seed N  --output FILE
+  render <puzzle.pa> <solution> write the solution as an animated GIF
+      --output FILE  --scale N  --delay N  --frames-per-move N
+      --easing linear|ease-in|ease-out|ease-in-out  --motion-blur N
+  verify <puzzle.pa> <solution> check that a solution solves the puzzle
+  bench                         solve many shuffled boards
+      --level easy|medium|hard  --count N  --shuffle N  --node-limit N
+      --heuristic NAME  --seed N  --beam-width N  --threads N
+      --mode greedy|astar|ida-star|anytime|beam|bidirectional
+  pdb <puzzle.pa>               build a pattern database for the goal board
+      --state-limit N  --output FILE
+  convert <image>               build a puzzle from a PNG or PPM image
+      --width N  --colors N  --quantizer median-cut|k-means
+      --dither none|ordered|floyd-steinberg  --start bands|canvas
+      --title TEXT  --output FILE";
 
 fn main() {
-    let seed = 0;
-    let mut rng = Pcg32::new(seed, 0xa02bdbf7bb3c0a7);
-
-    let goal = slidart::Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
-    let mut board = goal.clone();
-    board.shuffle(30, &mut rng);
-    slidart::print_path(&board.path);
-    println!("distance: {}", slidart::compute_distance2(&board, &goal));
-    board.path = vec![];
-    board.print();
-    println!("=== search ===");
-    let mut solver = slidart::Solver::new(goal, board);
-    solver.show_progress = true;
-    solver.search();
-
-    if let Some(result) = solver.result {
-        println!("search nodes: {}", solver.open_node_count);
-        // println!("score: {}", score);
-        result.print();
-        println!("path len: {}", result.path.len());
-        result.clone().print_history();
-        // print_path(&result.path);
-    }
-
-    // search(board, &initial_board);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let args = &args[1..];
+    let result = match command.as_str() {
+        "solve" => solve(args),
+        "shuffle" => shuffle(args),
+        "render" => render(args),
+        "verify" => verify(args),
+        "bench" => bench(args),
+        "convert" => convert(args),
+        "pdb" => pdb(args),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            println!("\nheuristics:");
+            for h in slidart::heuristics() {
+                let admissible = if h.is_admissible() {
+                    " (admissible)"
+                } else {
+                    ""
+                };
+                println!("  {}{}", h.name(), admissible);
+            }
+            Ok(())
+        }
+        _ => {
+            eprintln!("unknown command {:?}\n\n{}", command, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Command-line arguments split into positional arguments and `--name value`
+/// options.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// `options` take a value, `flags` do not; anything else is rejected.
+    fn parse(args: &[String], options: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut result = Args {
+            positional: vec![],
+            options: vec![],
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                result.positional.push(arg.clone());
+                continue;
+            };
+            if flags.contains(&name) {
+                result.options.push((name.to_string(), None));
+            } else if options.contains(&name) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("--{} needs a value", name))?;
+                result.options.push((name.to_string(), Some(value.clone())));
+            } else {
+                return Err(format!("unknown option --{}", name));
+            }
+        }
+        Ok(result)
+    }
+
+    fn positional(&self, count: usize, usage: &str) -> Result<&[String], String> {
+        if self.positional.len() == count {
+            Ok(&self.positional)
+        } else {
+            Err(format!("usage: slidart {}", usage))
+        }
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.options.iter().rev().find(|(n, _)| n == name) {
+            Some((_, Some(value))) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value {:?} for --{}", value, name)),
+            _ => Ok(None),
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+}
+
+fn read_puzzle(path: &str) -> Result<PuzzleFile, String> {
+    let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    slidart::format::parse(&s).map_err(|e| format!("{}:{}", path, e))
+}
+
+fn read_moves(path: &str) -> Result<Vec<slidart::Move>, String> {
+    let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    slidart::parse_moves(&s).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn write_output(path: Option<&str>, content: &str) -> Result<(), String> {
+    match path {
+        Some(path) => std::fs::write(path, content).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+fn rng(seed: u64) -> Pcg32 {
+    Pcg32::new(seed, 0xa02bdbf7bb3c0a7)
+}
+
+fn heuristic(name: &str) -> Result<Box<dyn slidart::Heuristic>, String> {
+    slidart::heuristic_by_name(name).ok_or_else(|| {
+        let names: Vec<&str> = slidart::heuristics().iter().map(|h| h.name()).collect();
+        format!(
+            "unknown heuristic {:?}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+fn search_mode(name: Option<&str>) -> Result<SearchMode, String> {
+    match name {
+        None | Some("greedy") => Ok(SearchMode::Greedy),
+        Some("astar") => Ok(SearchMode::AStar),
+        Some("ida-star") => Ok(SearchMode::IdaStar),
+        Some("anytime") => Ok(SearchMode::Anytime),
+        Some("beam") => Ok(SearchMode::Beam),
+        Some("bidirectional") => Ok(SearchMode::Bidirectional),
+        Some(name) => Err(format!("unknown search mode {:?}", name)),
+    }
+}
+
+/// Whether the mode finds, or eventually proves, the shortest solution when
+/// given an admissible heuristic.
+fn is_optimal(mode: SearchMode) -> bool {
+    matches!(
+        mode,
+        SearchMode::AStar | SearchMode::IdaStar | SearchMode::Anytime
+    )
+}
+
+/// Optimal modes default to an admissible heuristic, the others to the fast
+/// `distance4`.
+fn default_heuristic(mode: SearchMode) -> String {
+    if is_optimal(mode) {
+        "assignment"
+    } else {
+        "distance4"
+    }
+    .to_string()
+}
+
+fn solve(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &[
+            "seed",
+            "node-limit",
+            "heuristic",
+            "moves",
+            "output",
+            "gif",
+            "pdb",
+            "mode",
+            "weight",
+            "time-limit",
+            "beam-width",
+            "threads",
+            "checkpoint",
+            "checkpoint-interval",
+            "resume",
+        ],
+        &["progress", "stats"],
+    )?;
+    let puzzle_path = &args.positional(1, "solve <puzzle.pa>")?[0];
+    let puzzle = read_puzzle(puzzle_path)?;
+    let database = match args.get::<String>("pdb")? {
+        Some(path) => {
+            let database =
+                slidart::PatternDatabase::load(&path).map_err(|e| format!("{}: {}", path, e))?;
+            if !database.matches_goal(&puzzle.goal) {
+                return Err(format!("{}: built for a different goal board", path));
+            }
+            Some(database)
+        }
+        None => None,
+    };
+    let mut solver = match args.get::<String>("resume")? {
+        Some(path) => {
+            // The search goes on with the settings it was saved with.
+            for name in ["seed", "heuristic", "mode", "weight", "beam-width"] {
+                if args.get::<String>(name)?.is_some() {
+                    return Err(format!("--{} cannot be changed when resuming", name));
+                }
+            }
+            // A saved pattern database has to be given again with --pdb.
+            let mut solver = match database {
+                Some(database) => Solver::load_checkpoint_with(&path, Box::new(database)),
+                None => Solver::load_checkpoint(&path),
+            }
+            .map_err(|e| format!("{}: {}", path, e))?;
+            let same = |a: &Board, b: &Board| a.width == b.width && a.cells == b.cells;
+            if !same(&solver.goal, &puzzle.goal) || !same(solver.start(), &puzzle.start) {
+                return Err(format!("{}: saved for a different puzzle", path));
+            }
+            solver.header = puzzle.header.clone();
+            solver.palette = puzzle.palette.clone();
+            solver
+        }
+        None => {
+            let mut solver = puzzle.solver().map_err(|e| e.to_string())?;
+            solver.rng = rng(args.get("seed")?.unwrap_or(0));
+            solver.random_walk = 0;
+            solver.mode = search_mode(args.get::<String>("mode")?.as_deref())?;
+            solver.weight = args.get("weight")?.unwrap_or(solver.weight);
+            solver.beam_width = args.get("beam-width")?.unwrap_or(solver.beam_width);
+            match database {
+                Some(database) => solver.set_heuristic(Box::new(database)),
+                None => solver.set_heuristic(heuristic(
+                    &args
+                        .get::<String>("heuristic")?
+                        .unwrap_or(default_heuristic(solver.mode)),
+                )?),
+            }
+            solver
+        }
+    };
+    solver.show_progress = args.flag("progress");
+    solver.measure_time = args.flag("stats");
+    solver.set_score_fn(Box::new(|board, distance| {
+        -distance * 100 - board.path.len() as isize
+    }));
+    if is_optimal(solver.mode) && !solver.heuristic.is_admissible() {
+        eprintln!(
+            "warning: {} is not admissible, the solution may not be optimal",
+            solver.heuristic.name()
+        );
+    }
+
+    // Keep searching until the solution fits the move budget, if there is
+    // one, or time runs out. Without a budget the anytime search goes on
+    // improving its solution, and the other modes stop at the first one.
+    let target = args.get::<usize>("moves")?.or(puzzle.header.moves);
+    let time_limit = args
+        .get::<f64>("time-limit")?
+        .map(std::time::Duration::from_secs_f64);
+    let threads = args.get("threads")?.unwrap_or(1);
+    if threads > 1 && solver.mode != SearchMode::Greedy {
+        return Err("--threads needs --mode greedy".to_string());
+    }
+    let node_limit = args.get("node-limit")?.unwrap_or(usize::MAX);
+    let checkpoint = args.get::<String>("checkpoint")?;
+    let checkpoint_interval = args.get("checkpoint-interval")?.unwrap_or(1_000_000);
+    if checkpoint.is_some() && solver.mode == SearchMode::IdaStar {
+        return Err("--checkpoint cannot save an ida-star search".to_string());
+    }
+    let time = std::time::Instant::now();
+    solver.deadline = time_limit.map(|limit| time + limit);
+    loop {
+        // Pause every `checkpoint_interval` nodes to save the search.
+        solver.open_node_limit = match checkpoint {
+            Some(_) => node_limit.min(solver.open_node_count.saturating_add(checkpoint_interval)),
+            None => node_limit,
+        };
+        let found = if threads > 1 {
+            solver.search_parallel(threads)
+        } else {
+            solver.search()
+        };
+        if !found {
+            if let Some(path) = &checkpoint {
+                solver
+                    .save_checkpoint(path)
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                if solver.report.termination == Some(Termination::NodeLimit)
+                    && solver.open_node_count < node_limit
+                {
+                    continue;
+                }
+            }
+            break;
+        }
+        let moves = solver.result.as_ref().unwrap().path.len();
+        if solver.show_progress {
+            eprintln!("found {} moves after {:?}", moves, time.elapsed());
+        }
+        let done = match target {
+            Some(target) => moves <= target,
+            None => solver.mode != SearchMode::Anytime,
+        };
+        if done {
+            break;
+        }
+    }
+
+    if args.flag("stats") {
+        eprint!("{}", solver.report);
+    }
+    let Some(result) = solver.result else {
+        return Err(format!(
+            "no solution found after {} nodes ({})",
+            solver.open_node_count,
+            solver.report.termination.unwrap()
+        ));
+    };
+    eprintln!(
+        "search nodes: {}, moves: {}",
+        solver.open_node_count,
+        result.path.len()
+    );
+    write_output(
+        args.get::<String>("output")?.as_deref(),
+        &(slidart::format_moves(&result.moves()) + "\n"),
+    )?;
+    if let Some(path) = args.get::<String>("gif")? {
+        gif::save_solution(&path, &result, &puzzle.palette, &gif::GifOptions::default())
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn shuffle(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["moves", "seed", "output"], &[])?;
+    let puzzle_path = &args.positional(1, "shuffle <puzzle.pa>")?[0];
+    let mut puzzle = read_puzzle(puzzle_path)?;
+    let mut board = puzzle.goal.clone();
+    board.shuffle(
+        args.get("moves")?.unwrap_or(30),
+        &mut rng(args.get("seed")?.unwrap_or(0)),
+    );
+    board.path.clear();
+    puzzle.start = board;
+    write_output(
+        args.get::<String>("output")?.as_deref(),
+        &puzzle.to_string(),
+    )
+}
+
+fn render(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &[
+            "output",
+            "scale",
+            "delay",
+            "frames-per-move",
+            "easing",
+            "motion-blur",
+        ],
+        &[],
+    )?;
+    let paths = args.positional(2, "render <puzzle.pa> <solution>")?;
+    let (puzzle_path, solution_path) = (&paths[0], &paths[1]);
+    let puzzle = read_puzzle(puzzle_path)?;
+    let moves = read_moves(solution_path)?;
+    let result = slidart::verify(&puzzle.start, &puzzle.goal, &moves)
+        .map_err(|e| format!("{}: {}", solution_path, e))?;
+
+    let frames_per_move = args.get("frames-per-move")?.unwrap_or(1);
+    let animation = anim::Animation {
+        frames_per_move,
+        easing: match args.get::<String>("easing")?.as_deref() {
+            None | Some("ease-in-out") => anim::Easing::EaseInOut,
+            Some("linear") => anim::Easing::Linear,
+            Some("ease-in") => anim::Easing::EaseIn,
+            Some("ease-out") => anim::Easing::EaseOut,
+            Some(name) => return Err(format!("unknown easing {:?}", name)),
+        },
+        motion_blur: args.get("motion-blur")?.unwrap_or(1),
+    };
+    let options = gif::GifOptions {
+        delay: args
+            .get("delay")?
+            .unwrap_or((20 / frames_per_move.max(1)) as u16),
+        scale: args.get("scale")?.unwrap_or(8),
+        ..Default::default()
+    };
+    let path = args
+        .get::<String>("output")?
+        .unwrap_or("output.gif".to_string());
+    let file = std::fs::File::create(&path).map_err(|e| format!("{}: {}", path, e))?;
+    gif::write_animation(
+        std::io::BufWriter::new(file),
+        &result,
+        &puzzle.palette,
+        &animation,
+        &options,
+    )
+    .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn verify(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &[], &[])?;
+    let paths = args.positional(2, "verify <puzzle.pa> <solution>")?;
+    let (puzzle_path, solution_path) = (&paths[0], &paths[1]);
+    let puzzle = read_puzzle(puzzle_path)?;
+    let moves = read_moves(solution_path)?;
+    slidart::verify(&puzzle.start, &puzzle.goal, &moves)
+        .map_err(|e| format!("{}: {}", solution_path, e))?;
+    println!("{}: ok, {} moves", solution_path, moves.len());
+    Ok(())
+}
+
+fn bench(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &[
+            "level",
+            "count",
+            "shuffle",
+            "node-limit",
+            "heuristic",
+            "seed",
+            "mode",
+            "beam-width",
+            "threads",
+        ],
+        &[],
+    )?;
+    args.positional(0, "bench")?;
+    let goal = match args.get::<String>("level")?.as_deref() {
+        Some("easy") => Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]),
+        Some("medium") => Board::new(
+            4,
+            vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1],
+        ),
+        None | Some("hard") => Board::new(
+            6,
+            vec![
+                0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 5, 5, 5, 5,
+                5, 5, 1, 1, 1, 1, 1, 1,
+            ],
+        ),
+        Some(level) => return Err(format!("unknown level {:?}", level)),
+    };
+    let count = args.get("count")?.unwrap_or(1000);
+    let shuffle = args.get("shuffle")?.unwrap_or(300);
+    let node_limit = args.get("node-limit")?.unwrap_or(10000);
+    let mode = search_mode(args.get::<String>("mode")?.as_deref())?;
+    let threads = args.get("threads")?.unwrap_or(1);
+    let heuristic_name = args
+        .get::<String>("heuristic")?
+        .unwrap_or(default_heuristic(mode));
+
+    let mut rng = rng(args.get("seed")?.unwrap_or(0));
+    let mut succeeded = 0;
+    let mut total_path_length = 0;
+    let time = std::time::Instant::now();
+    // Solve in chunks so that finished solvers and their closed sets can be
+    // dropped.
+    let mut remaining = count;
+    while remaining > 0 {
+        let chunk = remaining.min(threads * 4);
+        remaining -= chunk;
+        let mut solvers = vec![];
+        for _ in 0..chunk {
+            let mut board = goal.clone();
+            board.shuffle(shuffle, &mut rng);
+            board.path.clear();
+            let mut solver = Solver::new(goal.clone(), board);
+            solver.open_node_limit = node_limit;
+            solver.mode = mode;
+            solver.beam_width = args.get("beam-width")?.unwrap_or(solver.beam_width);
+            solver.set_heuristic(heuristic(&heuristic_name)?);
+            solvers.push(solver);
+        }
+        let found = slidart::search_batch(&mut solvers, threads);
+        for (solver, found) in solvers.iter().zip(found) {
+            if found {
+                succeeded += 1;
+                total_path_length += solver.result.as_ref().unwrap().path.len();
+            }
+        }
+    }
+
+    println!("result: {}/{}", succeeded, count);
+    println!(
+        "average path length: {}",
+        total_path_length as f64 / succeeded as f64
+    );
+    println!("time: {:?}", time.elapsed());
+    Ok(())
+}
+
+fn pdb(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["state-limit", "output"], &[])?;
+    let puzzle_path = &args.positional(1, "pdb <puzzle.pa>")?[0];
+    let puzzle = read_puzzle(puzzle_path)?;
+    let database = slidart::PatternDatabase::new(
+        &puzzle.goal,
+        args.get("state-limit")?
+            .unwrap_or(slidart::DEFAULT_STATE_LIMIT),
+    );
+    let groups: Vec<String> = database.groups().map(|g| format!("{:?}", g)).collect();
+    eprintln!("groups: {}", groups.join(" "));
+    let path = args
+        .get::<String>("output")?
+        .unwrap_or("output.pdb".to_string());
+    database.save(&path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn convert(args: &[String]) -> Result<(), String> {
+    use slidart::generate::{generate_start, StartStyle};
+    use slidart::import::{image_to_board, load_image, Dither, ImportOptions, Quantizer};
+
+    let args = Args::parse(
+        args,
+        &[
+            "width",
+            "colors",
+            "quantizer",
+            "dither",
+            "start",
+            "title",
+            "output",
+        ],
+        &[],
+    )?;
+    let image_path = &args.positional(1, "convert <image>")?[0];
+    let image = load_image(image_path).map_err(|e| format!("{}: {}", image_path, e))?;
+    let options = ImportOptions {
+        width: args.get("width")?.unwrap_or(16),
+        colors: args.get("colors")?.unwrap_or(4),
+        quantizer: match args.get::<String>("quantizer")?.as_deref() {
+            None | Some("median-cut") => Quantizer::MedianCut,
+            Some("k-means") => Quantizer::KMeans { iterations: 16 },
+            Some(name) => return Err(format!("unknown quantizer {:?}", name)),
+        },
+        dither: match args.get::<String>("dither")?.as_deref() {
+            None | Some("none") => Dither::None,
+            Some("ordered") => Dither::Ordered,
+            Some("floyd-steinberg") => Dither::FloydSteinberg,
+            Some(name) => return Err(format!("unknown dithering {:?}", name)),
+        },
+        ..Default::default()
+    };
+    let style = match args.get::<String>("start")?.as_deref() {
+        None | Some("canvas") => StartStyle::Canvas,
+        Some("bands") => StartStyle::Bands,
+        Some(name) => return Err(format!("unknown start style {:?}", name)),
+    };
+
+    let (goal, palette) = image_to_board(&image, &options).map_err(|e| e.to_string())?;
+    let start = generate_start(&goal, &palette, &style).map_err(|e| e.to_string())?;
+    let mut puzzle = PuzzleFile::new(start, goal);
+    puzzle.palette = palette;
+    puzzle.header.title = args.get("title")?;
+    write_output(
+        args.get::<String>("output")?.as_deref(),
+        &puzzle.to_string(),
+    )
+}
+
+#[test]
+fn test_solve_anytime() {
+    let solution = |extra: &[&str]| {
+        let path = std::env::temp_dir().join(format!("slidart-anytime-{}.txt", extra.len()));
+        let path = path.to_str().unwrap();
+        let mut args = vec!["smile.pa", "--mode", "anytime", "--output", path];
+        args.extend(extra);
+        solve(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        let moves = read_moves(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        moves.len()
+    };
+    // A budget stops the search at the first solution that fits.
+    let first = solution(&["--moves", "1000"]);
+    let improved = solution(&["--time-limit", "60"]);
+    assert!(improved < first, "{} < {}", improved, first);
 }
@@ -0,0 +1,213 @@
+use crate::Board;
+
+/// A move, named after the direction the empty cell travels in like
+/// `Board::move_up` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    /// The notation has an unexpected character at byte `position`.
+    InvalidNotation { position: usize },
+    /// The move at `index` would take the empty cell off the board.
+    IllegalMove { index: usize, mv: Move },
+}
+
+impl Move {
+    /// All moves in the order used by `Board::move_candidates`.
+    pub const ALL: [Move; 4] = [Move::Up, Move::Right, Move::Down, Move::Left];
+
+    pub fn to_char(self) -> char {
+        match self {
+            Move::Up => 'U',
+            Move::Right => 'R',
+            Move::Down => 'D',
+            Move::Left => 'L',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'U' => Some(Move::Up),
+            'R' => Some(Move::Right),
+            'D' => Some(Move::Down),
+            'L' => Some(Move::Left),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Move::Up => Move::Down,
+            Move::Right => Move::Left,
+            Move::Down => Move::Up,
+            Move::Left => Move::Right,
+        }
+    }
+}
+
+impl Board {
+    /// Returns the cell the empty cell would move to, if the move stays on
+    /// the board.
+    pub fn neighbor(&self, mv: Move) -> Option<usize> {
+        let e = self.empty_cell;
+        match mv {
+            Move::Up if e >= self.width => Some(e - self.width),
+            Move::Right if (e + 1) % self.width != 0 => Some(e + 1),
+            Move::Down if e + self.width < self.cells.len() => Some(e + self.width),
+            Move::Left if e % self.width != 0 => Some(e - 1),
+            _ => None,
+        }
+    }
+
+    /// Returns the moves recorded in `path`.
+    pub fn moves(&self) -> Vec<Move> {
+        let empty_cells = self.path.iter().chain(std::iter::once(&self.empty_cell));
+        self.path
+            .iter()
+            .zip(empty_cells.skip(1))
+            .map(|(from, to)| {
+                if *to + self.width == *from {
+                    Move::Up
+                } else if *to == *from + 1 {
+                    Move::Right
+                } else if *to == *from + self.width {
+                    Move::Down
+                } else {
+                    Move::Left
+                }
+            })
+            .collect()
+    }
+
+    /// Applies `moves` in order. On an illegal move the board is left after
+    /// the moves preceding it.
+    pub fn apply_moves(&mut self, moves: &[Move]) -> Result<(), MoveError> {
+        for (index, mv) in moves.iter().enumerate() {
+            let next = self
+                .neighbor(*mv)
+                .ok_or(MoveError::IllegalMove { index, mv: *mv })?;
+            self.move_to(next);
+        }
+        Ok(())
+    }
+
+    pub fn apply_notation(&mut self, notation: &str) -> Result<(), MoveError> {
+        self.apply_moves(&parse_moves(notation)?)
+    }
+}
+
+/// Writes `moves` as `U`/`R`/`D`/`L` letters, each followed by a repeat count
+/// when it occurs more than once in a row, e.g. `R3D2L`.
+pub fn format_moves(moves: &[Move]) -> String {
+    let mut s = String::new();
+    let mut i = 0;
+    while i < moves.len() {
+        let n = moves[i..].iter().take_while(|m| **m == moves[i]).count();
+        s.push(moves[i].to_char());
+        if n > 1 {
+            s += &n.to_string();
+        }
+        i += n;
+    }
+    s
+}
+
+/// Longest run of one move `parse_moves` accepts, far more than a row or
+/// column of any board to solve.
+const MAX_REPEAT: usize = 10_000;
+
+/// Parses the notation written by `format_moves`. Whitespace is ignored.
+pub fn parse_moves(notation: &str) -> Result<Vec<Move>, MoveError> {
+    let mut moves = vec![];
+    let mut chars = notation.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mv = Move::from_char(c).ok_or(MoveError::InvalidNotation { position })?;
+        let mut count = None;
+        while let Some((position, d)) = chars.peek().cloned() {
+            let Some(d) = d.to_digit(10) else {
+                break;
+            };
+            count = count
+                .unwrap_or(0usize)
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(d as usize))
+                .filter(|n| (1..=MAX_REPEAT).contains(n));
+            if count.is_none() {
+                return Err(MoveError::InvalidNotation { position });
+            }
+            chars.next();
+        }
+        moves.extend(std::iter::repeat_n(mv, count.unwrap_or(1)));
+    }
+    Ok(moves)
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoveError::InvalidNotation { position } => {
+                write!(f, "invalid move notation at offset {}", position)
+            }
+            MoveError::IllegalMove { index, mv } => write!(f, "move {} ({}) is illegal", index, mv),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[test]
+fn test_notation() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let start = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = start.clone();
+    board.shuffle(30, &mut rng);
+
+    let notation = format_moves(&board.moves());
+    let mut replayed = start.clone();
+    replayed.apply_notation(&notation).unwrap();
+    assert_eq!(replayed.cells, board.cells);
+    assert_eq!(replayed.path, board.path);
+
+    assert_eq!(
+        parse_moves("R3 D2L").unwrap(),
+        [Move::Right; 3]
+            .into_iter()
+            .chain([Move::Down; 2])
+            .chain([Move::Left])
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        parse_moves("R0"),
+        Err(MoveError::InvalidNotation { position: 1 })
+    );
+    assert_eq!(
+        parse_moves("U99999999999999999999999"),
+        Err(MoveError::InvalidNotation { position: 5 })
+    );
+    assert_eq!(
+        parse_moves("RX"),
+        Err(MoveError::InvalidNotation { position: 1 })
+    );
+    assert_eq!(
+        start.clone().apply_notation("RDU2"),
+        Err(MoveError::IllegalMove {
+            index: 3,
+            mv: Move::Up
+        })
+    );
+}
@@ -0,0 +1,113 @@
+use crate::{Board, BoardError};
+
+/// The cells of a board with each colour in as few bits as the colours
+/// need, for remembering many boards at once. Hashes by the board's Zobrist
+/// hash, so building a key costs no extra pass over the cells.
+#[derive(Debug, Clone)]
+pub struct PackedBoard {
+    hash: u64,
+    words: Box<[u64]>,
+}
+
+/// Bits needed per cell to tell all colours of `board` apart.
+pub fn bits_per_cell(board: &Board) -> u32 {
+    let max = board.cells.iter().cloned().max().unwrap_or(0);
+    (u8::BITS - max.leading_zeros()).max(1)
+}
+
+impl PackedBoard {
+    /// Packs `board`, whose colours must fit in `bits` bits. Cells do not
+    /// straddle words.
+    pub fn new(board: &Board, bits: u32) -> Self {
+        let per_word = (u64::BITS / bits) as usize;
+        let words = board
+            .cells
+            .chunks(per_word)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |word, (i, c)| word | (*c as u64) << (i as u32 * bits))
+            })
+            .collect();
+        Self {
+            hash: board.zobrist_hash(),
+            words,
+        }
+    }
+
+    /// Returns the `len` cells packed with `bits` bits each.
+    pub fn unpack(&self, len: usize, bits: u32) -> Vec<u8> {
+        let per_word = (u64::BITS / bits) as usize;
+        let mask = (1 << bits) - 1;
+        (0..len)
+            .map(|i| (self.words[i / per_word] >> ((i % per_word) as u32 * bits) & mask) as u8)
+            .collect()
+    }
+
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub(crate) fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Reads back the board `new` packed into `words`.
+    pub(crate) fn unpack_board(
+        words: Box<[u64]>,
+        width: usize,
+        len: usize,
+        bits: u32,
+    ) -> Result<Board, BoardError> {
+        Board::try_new(width, Self { hash: 0, words }.unpack(len, bits))
+    }
+}
+
+/// Number of words `PackedBoard::new` uses for `len` cells.
+pub(crate) fn word_count(len: usize, bits: u32) -> usize {
+    len.div_ceil((u64::BITS / bits) as usize)
+}
+
+impl PartialEq for PackedBoard {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.words == other.words
+    }
+}
+
+impl Eq for PackedBoard {}
+
+impl std::hash::Hash for PackedBoard {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+#[test]
+fn test_packed_board() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(
+        5,
+        (0..25).map(|i| (i % 6 + (i > 0) as usize) as u8).collect(),
+    );
+    let bits = bits_per_cell(&goal);
+    assert_eq!(bits, 3);
+    assert_eq!(PackedBoard::new(&goal, bits).words.len(), 2);
+    let mut board = goal.clone();
+    for _ in 0..100 {
+        board.shuffle(1, &mut rng);
+        let packed = PackedBoard::new(&board, bits);
+        assert_eq!(packed.unpack(board.cells.len(), bits), board.cells);
+
+        let fresh = Board::new(board.width, board.cells.to_vec());
+        assert_eq!(board.zobrist_hash(), fresh.zobrist_hash());
+    }
+    while !board.path.is_empty() {
+        board.undo();
+    }
+    assert_eq!(board.zobrist_hash(), goal.zobrist_hash());
+    assert_eq!(
+        PackedBoard::new(&board, bits),
+        PackedBoard::new(&goal, bits)
+    );
+}
@@ -0,0 +1,162 @@
+pub type Rgb = [u8; 3];
+
+/// Maps the symbols of a `.pa` file to cell values and colours.
+///
+/// The built-in symbols (`#`, `_`, `.` and the digits) keep their fixed
+/// values; any other symbol gets the next free value from 10 upward when it
+/// is declared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Palette {
+    pub entries: Vec<PaletteEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub symbol: char,
+    pub value: u8,
+    pub color: Option<Rgb>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteError {
+    DuplicateSymbol(char),
+    ReservedSymbol(char),
+    /// `symbol` stands for the same value as the already used `other`, so
+    /// the two could not be told apart when written back.
+    SharedValue {
+        symbol: char,
+        other: char,
+    },
+    UnknownSymbol(char),
+    TooManyColors,
+}
+
+pub fn symbol_to_value(c: char) -> Option<u8> {
+    match c {
+        '#' => Some(0),
+        '_' => Some(1),
+        '.' => Some(2),
+        '0'..='9' => Some(c as u8 - b'0'),
+        _ => None,
+    }
+}
+
+pub fn value_to_symbol(value: u8) -> Option<char> {
+    match value {
+        0 => Some('#'),
+        1..=9 => Some((b'0' + value) as char),
+        _ => None,
+    }
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares `symbol` with an optional colour and returns its cell value.
+    pub fn declare(&mut self, symbol: char, color: Option<Rgb>) -> Result<u8, PaletteError> {
+        if symbol.is_whitespace() || symbol == ':' || symbol == '/' {
+            return Err(PaletteError::ReservedSymbol(symbol));
+        }
+        if self.entries.iter().any(|e| e.symbol == symbol) {
+            return Err(PaletteError::DuplicateSymbol(symbol));
+        }
+        let value = match symbol_to_value(symbol) {
+            Some(value) => value,
+            None => (10..=255)
+                .find(|v| self.entries.iter().all(|e| e.value != *v))
+                .ok_or(PaletteError::TooManyColors)?,
+        };
+        self.check_shared(symbol, value)?;
+        self.entries.push(PaletteEntry {
+            symbol,
+            value,
+            color,
+        });
+        Ok(value)
+    }
+
+    /// Returns the cell value of `symbol`, recording built-in symbols the
+    /// first time they are seen so they are written back unchanged.
+    pub fn resolve(&mut self, symbol: char) -> Result<u8, PaletteError> {
+        if let Some(value) = self.value(symbol) {
+            return Ok(value);
+        }
+        let value = symbol_to_value(symbol).ok_or(PaletteError::UnknownSymbol(symbol))?;
+        self.check_shared(symbol, value)?;
+        self.entries.push(PaletteEntry {
+            symbol,
+            value,
+            color: None,
+        });
+        Ok(value)
+    }
+
+    fn check_shared(&self, symbol: char, value: u8) -> Result<(), PaletteError> {
+        match self.entries.iter().find(|e| e.value == value) {
+            Some(e) => Err(PaletteError::SharedValue {
+                symbol,
+                other: e.symbol,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn value(&self, symbol: char) -> Option<u8> {
+        self.entries
+            .iter()
+            .find(|e| e.symbol == symbol)
+            .map(|e| e.value)
+    }
+
+    pub fn symbol(&self, value: u8) -> Option<char> {
+        self.entries
+            .iter()
+            .find(|e| e.value == value)
+            .map(|e| e.symbol)
+            .or_else(|| value_to_symbol(value))
+    }
+
+    /// Returns the declared colour of `value`, or a grey level scaled against
+    /// `max_value` like the old `.pgm` output.
+    pub fn color(&self, value: u8, max_value: u8) -> Rgb {
+        self.entries
+            .iter()
+            .find(|e| e.value == value && e.color.is_some())
+            .and_then(|e| e.color)
+            .unwrap_or_else(|| {
+                let l = (value as usize * 255 / max_value.max(1) as usize) as u8;
+                [l, l, l]
+            })
+    }
+}
+
+pub fn parse_color(s: &str) -> Option<Rgb> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 || !s.is_ascii() {
+        return None;
+    }
+    let c = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).ok();
+    Some([c(0)?, c(2)?, c(4)?])
+}
+
+pub fn format_color(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+impl std::fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PaletteError::DuplicateSymbol(c) => write!(f, "symbol {:?} is declared twice", c),
+            PaletteError::ReservedSymbol(c) => write!(f, "symbol {:?} is reserved", c),
+            PaletteError::SharedValue { symbol, other } => {
+                write!(f, "symbol {:?} has the same value as {:?}", symbol, other)
+            }
+            PaletteError::UnknownSymbol(c) => write!(f, "unknown symbol {:?}", c),
+            PaletteError::TooManyColors => write!(f, "too many colours in palette"),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
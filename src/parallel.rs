@@ -0,0 +1,467 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+
+use rand_core::RngCore;
+
+use crate::solver::{timed, PathTree, DEADLINE_INTERVAL, ROOT};
+use crate::{
+    check_mate, Board, Move, PackedBoard, ProgressFn, SearchMode, SearchProgress, SearchReport,
+    Solver, State,
+};
+
+/// Closed-set shards per thread, to keep lock contention low.
+const SHARDS_PER_THREAD: usize = 4;
+
+/// Nodes in the first chunk of `SharedPaths`; every further chunk is twice
+/// the size of the one before.
+const FIRST_CHUNK: u64 = 1 << 12;
+
+/// What the threads of `Solver::search_parallel` share.
+struct Shared {
+    frontier: Mutex<Frontier>,
+    /// Signalled when boards are pushed, the last busy thread finishes or
+    /// the search stops, to wake threads waiting for boards.
+    changed: Condvar,
+    closed: Vec<Mutex<HashMap<PackedBoard, usize>>>,
+    paths: SharedPaths,
+    best_state: Mutex<State>,
+    result: Mutex<Option<Board>>,
+    progress: Mutex<Option<ProgressFn>>,
+    nodes: AtomicUsize,
+    stop: AtomicBool,
+}
+
+struct Frontier {
+    states: BinaryHeap<State>,
+    /// Threads holding a board they have not finished expanding. The search
+    /// is exhausted once the frontier is empty and no thread is busy.
+    busy: usize,
+}
+
+/// The `PathTree` of a parallel search. New nodes go into chunks that are
+/// never moved or resized, so threads restore paths while others add to the
+/// tree, without a lock.
+struct SharedPaths {
+    /// The nodes from before the search, left alone until it ends.
+    tree: PathTree,
+    chunks: [OnceLock<Box<[Node]>>; (u64::BITS - FIRST_CHUNK.trailing_zeros()) as usize],
+    /// Nodes added, some of which may still be being written.
+    len: AtomicU64,
+}
+
+/// Nodes are written once, before the state that ends in them is pushed to
+/// the frontier, and read only by threads that popped a state below them.
+/// The frontier's lock orders the two, so relaxed accesses are enough.
+#[derive(Default)]
+struct Node {
+    parent: AtomicU64,
+    cell: AtomicU32,
+}
+
+impl SharedPaths {
+    fn new(tree: PathTree) -> Self {
+        Self {
+            tree,
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            len: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the chunk and the index in it of the new node `index`.
+    fn locate(index: u64) -> (usize, usize) {
+        let chunk = (index / FIRST_CHUNK + 1).ilog2();
+        let first = FIRST_CHUNK * ((1 << chunk) - 1);
+        (chunk as usize, (index - first) as usize)
+    }
+
+    fn new_node(&self, index: u64) -> &Node {
+        let (chunk, offset) = Self::locate(index);
+        let chunk = self.chunks[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| Node::default()).collect());
+        &chunk[offset]
+    }
+
+    fn node(&self, node: u64) -> (u64, u32) {
+        let base = self.tree.nodes.len() as u64;
+        if node < base {
+            return self.tree.nodes[node as usize];
+        }
+        let node = self.new_node(node - base);
+        (
+            node.parent.load(Ordering::Relaxed),
+            node.cell.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Like `PathTree::state`.
+    fn state(
+        &self,
+        mut node: u64,
+        moves: usize,
+        board: &Board,
+        score: isize,
+        distance: Option<isize>,
+        bits: u32,
+    ) -> State {
+        let cells = &board.path[moves..];
+        let first = self.len.fetch_add(cells.len() as u64, Ordering::Relaxed);
+        for (index, cell) in (first..).zip(cells) {
+            let new_node = self.new_node(index);
+            new_node.parent.store(node, Ordering::Relaxed);
+            new_node.cell.store(*cell as u32, Ordering::Relaxed);
+            node = self.tree.nodes.len() as u64 + index;
+        }
+        State {
+            board: PackedBoard::new(board, bits),
+            node,
+            moves: board.path.len(),
+            score,
+            distance,
+        }
+    }
+
+    /// Like `PathTree::restore`.
+    fn restore(&self, mut node: u64, prefix: &[usize], board: &mut Board) {
+        board.path.clear();
+        while node != ROOT {
+            let (parent, cell) = self.node(node);
+            board.path.push(cell as usize);
+            node = parent;
+        }
+        board.path.extend(prefix.iter().rev());
+        board.path.reverse();
+    }
+
+    /// Returns the tree with the new nodes added, once all threads are done.
+    fn into_tree(mut self) -> PathTree {
+        let len = *self.len.get_mut();
+        for index in 0..len {
+            let (chunk, offset) = Self::locate(index);
+            let node = &self.chunks[chunk].get().unwrap()[offset];
+            self.tree.nodes.push((
+                node.parent.load(Ordering::Relaxed),
+                node.cell.load(Ordering::Relaxed),
+            ));
+        }
+        self.tree
+    }
+}
+
+impl Shared {
+    fn shard(&self, key: &PackedBoard) -> &Mutex<HashMap<PackedBoard, usize>> {
+        &self.closed[(key.zobrist_hash() % self.closed.len() as u64) as usize]
+    }
+
+    /// Takes the best board, waiting while other threads may still add
+    /// some. Returns `None` once the search stopped or ran out of boards.
+    fn pop(&self, peak_frontier: &mut usize) -> Option<State> {
+        let mut frontier = self.frontier.lock().unwrap();
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            *peak_frontier = (*peak_frontier).max(frontier.states.len());
+            if let Some(state) = frontier.states.pop() {
+                frontier.busy += 1;
+                return Some(state);
+            }
+            if frontier.busy == 0 {
+                return None;
+            }
+            frontier = self.changed.wait(frontier).unwrap();
+        }
+    }
+
+    /// Adds the children of a board the calling thread has finished.
+    fn finish(&self, children: Vec<State>) {
+        let mut frontier = self.frontier.lock().unwrap();
+        frontier.states.extend(children);
+        frontier.busy -= 1;
+        self.changed.notify_all();
+    }
+
+    /// Stops all threads, the calling one having finished its board.
+    fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let mut frontier = self.frontier.lock().unwrap();
+        frontier.busy -= 1;
+        self.changed.notify_all();
+    }
+}
+
+impl<R: RngCore + Sync> Solver<R> {
+    /// Runs the greedy search on `threads` threads that share the frontier
+    /// and the closed set. Apart from the order boards are expanded in, it
+    /// behaves like `search` in `SearchMode::Greedy`, and can be resumed the
+    /// same way.
+    ///
+    /// Only the greedy search runs in parallel: this panics if `mode` is
+    /// anything other than `SearchMode::Greedy`.
+    pub fn search_parallel(&mut self, threads: usize) -> bool {
+        assert_eq!(
+            self.mode,
+            SearchMode::Greedy,
+            "search_parallel only supports the greedy search"
+        );
+        let threads = threads.max(1);
+        let shared = Shared {
+            frontier: Mutex::new(Frontier {
+                states: std::mem::take(&mut self.states),
+                busy: 0,
+            }),
+            changed: Condvar::new(),
+            closed: (0..threads * SHARDS_PER_THREAD)
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+            paths: SharedPaths::new(std::mem::take(&mut self.paths)),
+            best_state: Mutex::new(self.best_state.clone()),
+            result: Mutex::new(None),
+            progress: Mutex::new(self.progress.take()),
+            nodes: AtomicUsize::new(self.open_node_count),
+            stop: AtomicBool::new(false),
+        };
+        for (key, moves) in self.closed.drain() {
+            shared.shard(&key).lock().unwrap().insert(key, moves);
+        }
+        let seeds: Vec<u64> = (0..threads).map(|_| self.rng.next_u64()).collect();
+        let time = std::time::Instant::now();
+        self.started.get_or_insert(time);
+
+        let solver = &*self;
+        let reports: Vec<SearchReport> = std::thread::scope(|scope| {
+            let workers: Vec<_> = seeds
+                .into_iter()
+                .map(|seed| {
+                    let shared = &shared;
+                    scope.spawn(move || solver.parallel_worker(shared, seed))
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        self.states = shared.frontier.into_inner().unwrap().states;
+        for shard in shared.closed {
+            self.closed.extend(shard.into_inner().unwrap());
+        }
+        self.paths = shared.paths.into_tree();
+        self.best_state = shared.best_state.into_inner().unwrap();
+        self.progress = shared.progress.into_inner().unwrap();
+        self.open_node_count = shared.nodes.into_inner();
+        for report in reports {
+            self.report.expanded += report.expanded;
+            self.report.generated += report.generated;
+            self.report.duplicates += report.duplicates;
+            self.report.peak_frontier = self.report.peak_frontier.max(report.peak_frontier);
+            self.report.heuristic_time += report.heuristic_time;
+            self.report.check_mate_time += report.check_mate_time;
+        }
+        self.report.peak_closed = self.report.peak_closed.max(self.closed.len());
+        let result = shared.result.into_inner().unwrap();
+        let found = result.is_some();
+        if found {
+            self.result = result;
+        }
+        self.finish_report(found, time.elapsed());
+        found
+    }
+
+    /// Returns the statistics of this thread; closed-set sizes are left to
+    /// the caller.
+    fn parallel_worker(&self, shared: &Shared, seed: u64) -> SearchReport {
+        let mut rng = rand_pcg::Pcg32::new(seed, 0xa02bdbf7bb3c0a7);
+        let mut report = SearchReport::default();
+        while let Some(state) = shared.pop(&mut report.peak_frontier) {
+            let nodes = shared.nodes.fetch_add(1, Ordering::SeqCst);
+            if nodes >= self.open_node_limit
+                || self.cancelled()
+                || nodes % DEADLINE_INTERVAL == 0 && self.deadline_passed()
+            {
+                shared.nodes.fetch_sub(1, Ordering::SeqCst);
+                shared.frontier.lock().unwrap().states.push(state);
+                shared.stop();
+                return report;
+            }
+
+            let mut board = self.unpack(&state.board);
+            let State {
+                node,
+                moves,
+                distance,
+                score,
+                ..
+            } = state;
+            shared.paths.restore(node, &self.start.path, &mut board);
+            report.expanded += 1;
+            let distance = distance.unwrap_or_else(|| {
+                timed(self.measure_time, &mut report.heuristic_time, || {
+                    self.heuristic.evaluate(&board, &self.goal)
+                })
+            });
+            if (nodes + 1) % self.progress_interval.max(1) == 0 {
+                if let Some(progress) = shared.progress.lock().unwrap().as_mut() {
+                    let frontier = shared.frontier.lock().unwrap().states.len();
+                    let best_state = shared.best_state.lock().unwrap().clone();
+                    let mut best_board = self.unpack(&best_state.board);
+                    shared
+                        .paths
+                        .restore(best_state.node, &self.start.path, &mut best_board);
+                    progress(&SearchProgress {
+                        nodes: nodes + 1,
+                        frontier,
+                        best_score: best_state.score,
+                        best_board: &best_board,
+                        elapsed: self.started.map(|t| t.elapsed()).unwrap_or_default(),
+                    });
+                }
+            }
+            if self.show_progress && (nodes + 1) % 10000 == 0 {
+                println!(
+                    "open_node_count: {:>6}, distance: {:>3}, current score: {:>5}",
+                    nodes + 1,
+                    distance,
+                    score
+                );
+            }
+            if let Some(result) = timed(self.measure_time, &mut report.check_mate_time, || {
+                check_mate(&mut board, &self.goal, self.check_mate_cutoff)
+            }) {
+                shared.result.lock().unwrap().get_or_insert(result);
+                shared.stop();
+                return report;
+            }
+
+            let mut children = vec![];
+            // `moved` is the tile's move from the expanded board, if any, for
+            // incremental evaluation.
+            let mut add_state = |child: Board, moved: Option<(usize, usize)>| {
+                report.generated += 1;
+                {
+                    let key = PackedBoard::new(&child, self.bits);
+                    let mut shard = shared.shard(&key).lock().unwrap();
+                    if shard.contains_key(&key) {
+                        report.duplicates += 1;
+                        return;
+                    }
+                    shard.insert(key, child.path.len());
+                }
+                let distance = timed(self.measure_time, &mut report.heuristic_time, || {
+                    moved
+                        .and_then(|(from, to)| {
+                            self.heuristic
+                                .update(&child, &self.goal, distance, from, to)
+                        })
+                        .unwrap_or_else(|| self.heuristic.evaluate(&child, &self.goal))
+                });
+                let score = (self.score_fn)(&child, distance);
+                children.push(shared.paths.state(
+                    node,
+                    moves,
+                    &child,
+                    score,
+                    Some(distance),
+                    self.bits,
+                ));
+            };
+            for next in Move::ALL.iter().filter_map(|mv| board.neighbor(*mv)) {
+                let mut child = board.clone();
+                child.move_to(next);
+                add_state(child, Some((next, board.empty_cell)));
+            }
+            for _ in 0..self.random_walk {
+                let mut child = board.clone();
+                child.shuffle(self.random_walk_len, &mut rng);
+                add_state(child, None);
+            }
+            if let Some(best) = children.iter().max() {
+                let mut best_state = shared.best_state.lock().unwrap();
+                if best.score > best_state.score {
+                    *best_state = best.clone();
+                }
+            }
+            shared.finish(children);
+        }
+        report
+    }
+}
+
+/// Runs `search` on every solver, spreading them over `threads` threads, and
+/// returns what each call returned.
+pub fn search_batch<R: RngCore + Send>(solvers: &mut [Solver<R>], threads: usize) -> Vec<bool> {
+    let results = Mutex::new(vec![false; solvers.len()]);
+    let queue = Mutex::new(solvers.iter_mut().enumerate());
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let Some((i, solver)) = queue.lock().unwrap().next() else {
+                    return;
+                };
+                let found = solver.search();
+                results.lock().unwrap()[i] = found;
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+#[test]
+fn test_shared_paths() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let bits = crate::bits_per_cell(&goal);
+    let mut board = goal.clone();
+    board.shuffle(10, &mut rng);
+    let mut tree = PathTree::default();
+    let first = tree.state(ROOT, 0, &board, 0, None, bits);
+
+    // Long enough to fill several chunks.
+    let paths = SharedPaths::new(tree);
+    board.shuffle(5 * FIRST_CHUNK as usize, &mut rng);
+    let state = paths.state(first.node, first.moves, &board, 0, None, bits);
+    let mut restored = board.clone();
+    paths.restore(state.node, &[], &mut restored);
+    assert_eq!(restored.path, board.path);
+    restored.path.clear();
+    paths.into_tree().restore(state.node, &[], &mut restored);
+    assert_eq!(restored.path, board.path);
+}
+
+#[test]
+fn test_parallel_search() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let boards: Vec<Board> = (0..8)
+        .map(|_| {
+            let mut board = goal.clone();
+            board.shuffle(300, &mut rng);
+            board.path.clear();
+            board
+        })
+        .collect();
+
+    let mut solver = Solver::new(goal.clone(), boards[0].clone());
+    solver.open_node_limit = 20;
+    assert!(!solver.search_parallel(4));
+    assert_eq!(solver.open_node_count, 20);
+    solver.open_node_limit = usize::MAX;
+    assert!(solver.search_parallel(4));
+    crate::verify(&boards[0], &goal, &solver.result.unwrap().moves()).unwrap();
+
+    let mut solvers: Vec<_> = boards
+        .iter()
+        .map(|board| Solver::new(goal.clone(), board.clone()))
+        .collect();
+    assert!(search_batch(&mut solvers, 3).into_iter().all(|found| found));
+    for (solver, board) in solvers.iter().zip(&boards) {
+        crate::verify(board, &goal, &solver.result.as_ref().unwrap().moves()).unwrap();
+    }
+}
+
+#[test]
+#[should_panic(expected = "only supports the greedy search")]
+fn test_parallel_search_mode() {
+    let goal = Board::new(2, vec![0, 1, 2, 3]);
+    let mut solver = Solver::new(goal.clone(), goal);
+    solver.mode = SearchMode::AStar;
+    solver.search_parallel(2);
+}
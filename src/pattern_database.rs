@@ -0,0 +1,571 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::OnceLock;
+
+use crate::{Board, Heuristic};
+
+/// Default cap on the states searched for one group, including the empty
+/// cell position. The search keeps one byte per state.
+pub const DEFAULT_STATE_LIMIT: usize = 1 << 22;
+
+const MAGIC: &[u8; 5] = b"SLPDB";
+const VERSION: u8 = 1;
+
+/// Additive pattern database.
+///
+/// The goal's colours are split into groups. For each group, a 0-1
+/// breadth-first search backward from the goal finds how many moves of the
+/// group's tiles any placement of them needs, with all other tiles treated
+/// as interchangeable and free to move. Since no move is counted by two
+/// groups, the sum over groups never overestimates.
+///
+/// Colours that do not fit into the state limit on their own are left out.
+#[derive(Default)]
+pub struct PatternDatabase {
+    goal: Option<Board>,
+    groups: Vec<Group>,
+    /// Group and symbol of every colour in a group.
+    symbols: Vec<Option<(usize, u8)>>,
+    state_limit: Option<usize>,
+    /// Tables built on first use for a goal that `prepare` was not called
+    /// with.
+    unprepared: OnceLock<Box<PatternDatabase>>,
+}
+
+struct Group {
+    colors: Vec<u8>,
+    /// Number of cells per symbol, with symbol 0 for cells outside the
+    /// group, the empty one included.
+    counts: Vec<usize>,
+    /// Moves needed for every placement, by rank.
+    table: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum PatternDatabaseError {
+    Io(std::io::Error),
+    InvalidFormat,
+    UnsupportedVersion(u8),
+}
+
+impl PatternDatabase {
+    /// Builds tables for colour groups chosen by `partition`.
+    pub fn new(goal: &Board, state_limit: usize) -> Self {
+        let mut database = Self::build(goal, &Self::partition(goal, state_limit));
+        database.state_limit = Some(state_limit);
+        database
+    }
+
+    /// Returns a database that `prepare` builds with `state_limit` instead
+    /// of `DEFAULT_STATE_LIMIT`.
+    pub fn with_state_limit(state_limit: usize) -> Self {
+        Self {
+            state_limit: Some(state_limit),
+            ..Default::default()
+        }
+    }
+
+    /// Builds tables for the given colour groups, which must not overlap.
+    pub fn build(goal: &Board, groups: &[Vec<u8>]) -> Self {
+        let groups = groups
+            .iter()
+            .map(|colors| {
+                let counts = group_counts(goal, colors);
+                let table = search(goal, colors, &counts);
+                Group {
+                    colors: colors.clone(),
+                    counts,
+                    table,
+                }
+            })
+            .collect();
+        Self::from_groups(goal.clone(), groups)
+    }
+
+    fn from_groups(goal: Board, groups: Vec<Group>) -> Self {
+        let mut symbols = vec![None; 256];
+        for (g, group) in groups.iter().enumerate() {
+            for (s, c) in group.colors.iter().enumerate() {
+                symbols[*c as usize] = Some((g, s as u8 + 1));
+            }
+        }
+        PatternDatabase {
+            goal: Some(goal),
+            groups,
+            symbols,
+            state_limit: None,
+            unprepared: OnceLock::new(),
+        }
+    }
+
+    /// Splits the goal's colours, in order of value, into groups that each
+    /// need at most `state_limit` search states.
+    pub fn partition(goal: &Board, state_limit: usize) -> Vec<Vec<u8>> {
+        let mut colors: Vec<u8> = goal.cells.iter().cloned().filter(|c| *c != 0).collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        let fits = |colors: &[u8]| {
+            let mut counts = group_counts(goal, colors);
+            counts[0] -= 1;
+            counts.push(1);
+            multinomial(&counts).is_some_and(|n| n <= state_limit as u64)
+        };
+        let mut groups: Vec<Vec<u8>> = vec![];
+        for c in colors {
+            match groups.last_mut() {
+                Some(group) if fits(&[&group[..], &[c]].concat()) => group.push(c),
+                _ if fits(&[c]) => groups.push(vec![c]),
+                _ => {}
+            }
+        }
+        groups
+    }
+
+    /// Whether the tables were built for `goal`.
+    pub fn matches_goal(&self, goal: &Board) -> bool {
+        self.goal
+            .as_ref()
+            .is_some_and(|g| g.width == goal.width && g.cells == goal.cells)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &[u8]> {
+        self.groups.iter().map(|g| &g.colors[..])
+    }
+
+    /// Symbol of colour `c` in `group`.
+    fn label(&self, group: usize, c: u8) -> u8 {
+        match self.symbols[c as usize] {
+            Some((g, s)) if g == group => s,
+            _ => 0,
+        }
+    }
+
+    fn group_value(&self, group: usize, cells: &[u8]) -> isize {
+        let counts = &self.groups[group].counts;
+        let mut remaining = [0; 256];
+        let remaining = &mut remaining[..counts.len()];
+        remaining.copy_from_slice(counts);
+        let rank = rank_terms(cells.iter().map(|c| self.label(group, *c)), remaining);
+        self.groups[group].table[rank as usize] as isize
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let goal = self.goal.as_ref().expect("pattern database is not built");
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&(goal.width as u32).to_le_bytes())?;
+        writer.write_all(&(goal.cells.len() as u32).to_le_bytes())?;
+        writer.write_all(&goal.cells)?;
+        writer.write_all(&(self.groups.len() as u32).to_le_bytes())?;
+        for group in &self.groups {
+            writer.write_all(&[group.colors.len() as u8])?;
+            writer.write_all(&group.colors)?;
+            writer.write_all(&(group.table.len() as u64).to_le_bytes())?;
+            writer.write_all(&group.table)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> Result<Self, PatternDatabaseError> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PatternDatabaseError::InvalidFormat);
+        }
+        let version = read_bytes(&mut reader, 1)?[0];
+        if version != VERSION {
+            return Err(PatternDatabaseError::UnsupportedVersion(version));
+        }
+        let width = read_u32(&mut reader)? as usize;
+        let len = read_u32(&mut reader)? as usize;
+        if width == 0 || len % width != 0 {
+            return Err(PatternDatabaseError::InvalidFormat);
+        }
+        let cells = read_bytes(&mut reader, len)?;
+        let goal = Board::try_new(width, cells).map_err(|_| PatternDatabaseError::InvalidFormat)?;
+
+        let mut groups = vec![];
+        let mut seen = [false; 256];
+        for _ in 0..read_u32(&mut reader)? {
+            let color_count = read_bytes(&mut reader, 1)?[0] as usize;
+            let colors = read_bytes(&mut reader, color_count)?;
+            for c in &colors {
+                if *c == 0 || seen[*c as usize] {
+                    return Err(PatternDatabaseError::InvalidFormat);
+                }
+                seen[*c as usize] = true;
+            }
+            let counts = group_counts(&goal, &colors);
+            let table_len = u64::from_le_bytes(read_bytes(&mut reader, 8)?.try_into().unwrap());
+            if multinomial(&counts) != Some(table_len) {
+                return Err(PatternDatabaseError::InvalidFormat);
+            }
+            let table = read_bytes(&mut reader, table_len as usize)?;
+            groups.push(Group {
+                colors,
+                counts,
+                table,
+            });
+        }
+        Ok(Self::from_groups(goal, groups))
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, PatternDatabaseError> {
+        let file = std::fs::File::open(path)?;
+        Self::read(std::io::BufReader::new(file))
+    }
+}
+
+/// Reads exactly `len` bytes. The buffer grows with the bytes actually
+/// read, so a corrupt length cannot allocate more than the input holds.
+pub(crate) fn read_bytes(reader: &mut impl Read, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    Ok(u32::from_le_bytes(
+        read_bytes(reader, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Cells per symbol of `colors`, after symbol 0 for all other cells.
+fn group_counts(goal: &Board, colors: &[u8]) -> Vec<usize> {
+    let mut counts = vec![0; colors.len() + 1];
+    for c in &goal.cells {
+        counts[colors.iter().position(|g| g == c).map_or(0, |s| s + 1)] += 1;
+    }
+    counts
+}
+
+/// Number of distinct sequences with `counts[s]` copies of each symbol `s`,
+/// or `None` if it does not fit in a `u64`.
+fn multinomial(counts: &[usize]) -> Option<u64> {
+    let mut result: u128 = 1;
+    let mut n = 0;
+    for count in counts {
+        for k in 1..=*count {
+            n += 1;
+            result = result.checked_mul(n)? / k as u128;
+        }
+    }
+    u64::try_from(result).ok()
+}
+
+/// Index of `labels` among all sequences with the symbol counts `counts`, in
+/// lexicographic order.
+fn rank(labels: &[u8], counts: &[usize]) -> u64 {
+    rank_terms(labels.iter().cloned(), &mut counts.to_vec())
+}
+
+/// Sums the terms of `rank` for `labels`, which continue a sequence whose
+/// remaining labels have the symbol counts `remaining`. The labels are
+/// taken off `remaining`, so that the rank of a whole sequence is the sum
+/// over consecutive parts of it.
+fn rank_terms(labels: impl Iterator<Item = u8>, remaining: &mut [usize]) -> u64 {
+    let mut total = multinomial(remaining).unwrap();
+    let mut n = remaining.iter().sum::<usize>() as u64;
+    let mut rank = 0;
+    for label in labels {
+        for count in &remaining[..label as usize] {
+            rank += total * *count as u64 / n;
+        }
+        total = total * remaining[label as usize] as u64 / n;
+        remaining[label as usize] -= 1;
+        n -= 1;
+    }
+    rank
+}
+
+fn unrank(mut rank: u64, counts: &[usize], labels: &mut [u8]) {
+    let mut remaining = counts.to_vec();
+    let mut total = multinomial(counts).unwrap();
+    for (n, label) in (1..=labels.len() as u64).rev().zip(labels.iter_mut()) {
+        for (s, count) in remaining.iter_mut().enumerate() {
+            let block = total * *count as u64 / n;
+            if rank < block {
+                *label = s as u8;
+                total = block;
+                *count -= 1;
+                break;
+            }
+            rank -= block;
+        }
+    }
+}
+
+/// Runs the 0-1 breadth-first search for one group and returns its table.
+fn search(goal: &Board, colors: &[u8], counts: &[usize]) -> Vec<u8> {
+    // The search tells the empty cell apart with one more symbol.
+    let empty = counts.len() as u8;
+    let mut search_counts = counts.to_vec();
+    search_counts[0] -= 1;
+    search_counts.push(1);
+    let label = |c: &u8| {
+        if *c == 0 {
+            empty
+        } else {
+            colors
+                .iter()
+                .position(|g| g == c)
+                .map_or(0, |s| s as u8 + 1)
+        }
+    };
+
+    let mut labels: Vec<u8> = goal.cells.iter().map(label).collect();
+    let mut distances = vec![u8::MAX; multinomial(&search_counts).unwrap() as usize];
+    let start = rank(&labels, &search_counts);
+    distances[start as usize] = 0;
+    let mut queue = VecDeque::from([start]);
+    let mut board = goal.clone();
+    while let Some(state) = queue.pop_front() {
+        let distance = distances[state as usize];
+        unrank(state, &search_counts, &mut labels);
+        board.empty_cell = labels.iter().position(|l| *l == empty).unwrap();
+        for next in [
+            board.empty_cell.checked_sub(board.width),
+            Some(board.empty_cell + 1).filter(|i| i % board.width != 0),
+            Some(board.empty_cell + board.width).filter(|i| *i < labels.len()),
+            board
+                .empty_cell
+                .checked_sub(1)
+                .filter(|_| board.empty_cell % board.width != 0),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let cost = (labels[next] != 0) as u8;
+            labels.swap(board.empty_cell, next);
+            let next_state = rank(&labels, &search_counts);
+            labels.swap(board.empty_cell, next);
+            // Saturating keeps long distances a lower bound.
+            let next_distance = distance.saturating_add(cost).min(u8::MAX - 1);
+            if next_distance < distances[next_state as usize] {
+                distances[next_state as usize] = next_distance;
+                if cost == 0 {
+                    queue.push_front(next_state);
+                } else {
+                    queue.push_back(next_state);
+                }
+            }
+        }
+    }
+
+    // Where the empty cell is does not matter for the lookup, so keep the
+    // best over its positions.
+    let mut table = vec![u8::MAX; multinomial(counts).unwrap() as usize];
+    for (state, distance) in distances.into_iter().enumerate() {
+        unrank(state as u64, &search_counts, &mut labels);
+        for l in labels.iter_mut().filter(|l| **l == empty) {
+            *l = 0;
+        }
+        let entry = &mut table[rank(&labels, counts) as usize];
+        *entry = (*entry).min(distance);
+    }
+    // Placements the search never reached cannot occur on a reachable board.
+    for entry in table.iter_mut().filter(|e| **e == u8::MAX) {
+        *entry = 0;
+    }
+    table
+}
+
+impl Heuristic for PatternDatabase {
+    fn name(&self) -> &'static str {
+        "pattern_database"
+    }
+
+    fn is_admissible(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        if !self.matches_goal(goal) {
+            let state_limit = self.state_limit.unwrap_or(DEFAULT_STATE_LIMIT);
+            *self = Self::new(goal, state_limit);
+        }
+    }
+
+    /// Builds the same colour groups for `goal`, or groups under the same
+    /// state limit if the groups were chosen by one.
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let database = match (self.state_limit, &self.goal) {
+            (None, Some(_)) => {
+                Self::build(goal, &self.groups().map(|g| g.to_vec()).collect::<Vec<_>>())
+            }
+            (limit, _) => Self::new(goal, limit.unwrap_or(DEFAULT_STATE_LIMIT)),
+        };
+        Box::new(database)
+    }
+
+    /// FNV-1a hash of the colour groups and their tables.
+    fn fingerprint(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for group in &self.groups {
+            let len = [group.colors.len() as u8];
+            for byte in len.iter().chain(&group.colors).chain(&group.table) {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        if !self.matches_goal(goal) {
+            return self
+                .unprepared
+                .get_or_init(|| {
+                    let state_limit = self.state_limit.unwrap_or(DEFAULT_STATE_LIMIT);
+                    Box::new(Self::new(goal, state_limit))
+                })
+                .evaluate(board, goal);
+        }
+        (0..self.groups.len())
+            .map(|g| self.group_value(g, &board.cells))
+            .sum()
+    }
+
+    /// Only the group of the moved tile changes. Its rank before and after
+    /// the move differs only in the terms for the cells from `from` to `to`,
+    /// so the rest of the board is ranked once for both.
+    fn update(
+        &self,
+        board: &Board,
+        goal: &Board,
+        previous: isize,
+        from: usize,
+        to: usize,
+    ) -> Option<isize> {
+        if !self.matches_goal(goal) {
+            return None;
+        }
+        let Some((group, _)) = self.symbols[board.cells[to] as usize] else {
+            return Some(previous);
+        };
+        let label = |i: usize| self.label(group, board.cells[i]);
+        let (a, b) = (from.min(to), from.max(to));
+        let Group { counts, table, .. } = &self.groups[group];
+
+        let mut remaining = [0; 256];
+        let remaining = &mut remaining[..counts.len()];
+        remaining.copy_from_slice(counts);
+        let head = rank_terms((0..a).map(label), remaining);
+        let mut before_remaining = [0; 256];
+        let before_remaining = &mut before_remaining[..counts.len()];
+        before_remaining.copy_from_slice(remaining);
+        let after = rank_terms((a..=b).map(label), remaining);
+        let before = rank_terms(
+            (a..=b).map(|i| {
+                label(if i == a {
+                    b
+                } else if i == b {
+                    a
+                } else {
+                    i
+                })
+            }),
+            before_remaining,
+        );
+        // The two boards agree past `b`.
+        let tail = rank_terms((b + 1..board.cells.len()).map(label), remaining);
+
+        let value = |rank: u64| table[(head + rank + tail) as usize] as isize;
+        Some(previous - value(before) + value(after))
+    }
+}
+
+impl From<std::io::Error> for PatternDatabaseError {
+    fn from(e: std::io::Error) -> Self {
+        PatternDatabaseError::Io(e)
+    }
+}
+
+impl std::fmt::Display for PatternDatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PatternDatabaseError::Io(e) => write!(f, "{}", e),
+            PatternDatabaseError::InvalidFormat => write!(f, "not a pattern database"),
+            PatternDatabaseError::UnsupportedVersion(v) => {
+                write!(f, "unsupported pattern database version {}", v)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternDatabaseError {}
+
+#[test]
+fn test_pattern_database() {
+    let counts = [2, 1, 1];
+    let mut labels = [0; 4];
+    for r in 0..multinomial(&counts).unwrap() {
+        unrank(r, &counts, &mut labels);
+        assert_eq!(rank(&labels, &counts), r);
+    }
+
+    let goal = Board::new(3, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    let database = PatternDatabase::new(&goal, 20_000);
+    assert_eq!(
+        database.groups().collect::<Vec<_>>(),
+        [&[1, 2, 3, 4][..], &[5, 6, 7, 8][..]]
+    );
+    assert_eq!(database.evaluate(&goal, &goal), 0);
+    // Both groups have to move one tile out of the way and back.
+    let board = Board::new(3, vec![1, 2, 3, 4, 5, 6, 8, 7, 0]);
+    assert!(database.evaluate(&board, &goal) >= 2 + 2);
+
+    let mut buf = vec![];
+    database.write(&mut buf).unwrap();
+    let loaded = PatternDatabase::read(&buf[..]).unwrap();
+    assert!(loaded.matches_goal(&goal));
+    assert_eq!(
+        loaded.evaluate(&board, &goal),
+        database.evaluate(&board, &goal)
+    );
+    // Searching backward builds the same groups for the other board.
+    let backward = loaded.for_goal(&board);
+    assert_eq!(
+        backward.evaluate(&goal, &board),
+        PatternDatabase::new(&board, 20_000).evaluate(&goal, &board)
+    );
+    // Another goal gets tables of its own on first use.
+    assert_eq!(
+        database.evaluate(&goal, &board),
+        PatternDatabase::new(&board, 20_000).evaluate(&goal, &board)
+    );
+    let custom = PatternDatabase::build(&goal, &[vec![1, 2], vec![7, 8]]);
+    assert_eq!(
+        custom.for_goal(&board).evaluate(&goal, &board),
+        PatternDatabase::build(&board, &[vec![1, 2], vec![7, 8]]).evaluate(&goal, &board)
+    );
+    buf[5] = VERSION + 1;
+    assert!(matches!(
+        PatternDatabase::read(&buf[..]),
+        Err(PatternDatabaseError::UnsupportedVersion(_))
+    ));
+    // A goal length larger than the file fails without allocating it.
+    buf[5] = VERSION;
+    buf[10..14].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+    assert!(matches!(
+        PatternDatabase::read(&buf[..]),
+        Err(PatternDatabaseError::Io(_))
+    ));
+
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let mut board = goal.clone();
+    for _ in 0..100 {
+        board.shuffle(1, &mut rng);
+        assert!(database.evaluate(&board, &goal) <= board.path.len() as isize);
+    }
+}
@@ -0,0 +1,156 @@
+use super::{render_board, replay, Image};
+use crate::{Board, Palette};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the linear progress `t` in `[0, 1]` to the eased progress.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Renders a solution with the moving tile sliding between cells.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Number of frames drawn for each move; the last one shows the tile in
+    /// its new cell.
+    pub frames_per_move: usize,
+    pub easing: Easing,
+    /// Number of time samples averaged into each frame. Values above 1 smear
+    /// the moving tile along its track.
+    pub motion_blur: usize,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frames_per_move: 4,
+            easing: Easing::EaseInOut,
+            motion_blur: 1,
+        }
+    }
+}
+
+impl Animation {
+    /// Returns the frames for `board.path`, starting with the start board.
+    pub fn render(&self, board: &Board, palette: &Palette, scale: usize) -> Vec<Image> {
+        self.frames(board, palette, scale).collect()
+    }
+
+    /// Like `render`, but draws the frames of each move only when the
+    /// iterator gets to it.
+    pub fn frames<'a>(
+        &'a self,
+        board: &Board,
+        palette: &'a Palette,
+        scale: usize,
+    ) -> impl Iterator<Item = Image> + 'a {
+        let boards = replay(board);
+        let max_value = board.cells.iter().cloned().max().unwrap_or(0);
+        let first = render_board(&boards[0], palette, scale);
+        std::iter::once(first).chain((1..boards.len()).flat_map(move |i| {
+            self.move_frames(&boards[i - 1], &boards[i], palette, max_value, scale)
+        }))
+    }
+
+    /// Renders the frames of the move from `prev` to `next`.
+    fn move_frames(
+        &self,
+        prev: &Board,
+        next: &Board,
+        palette: &Palette,
+        max_value: u8,
+        scale: usize,
+    ) -> Vec<Image> {
+        let frames_per_move = self.frames_per_move.max(1);
+        let samples = self.motion_blur.max(1);
+
+        // The tile moves from the new empty cell into the old one.
+        let (from, to) = (next.empty_cell, prev.empty_cell);
+        let color = palette.color(next.cells[to], max_value);
+        let mut background = render_board(next, palette, scale);
+        let (x, y) = next.index_to_xy(to);
+        background.fill_rect(
+            x * scale,
+            y * scale,
+            scale,
+            scale,
+            palette.color(0, max_value),
+        );
+
+        let mut frames = vec![];
+        for i in 0..frames_per_move {
+            let mut sum = vec![[0u32; 3]; background.pixels.len()];
+            for s in 0..samples {
+                let t = (i as f64 + (s + 1) as f64 / samples as f64) / frames_per_move as f64;
+                let mut image = background.clone();
+                let (x, y) = self.tile_position(next, from, to, t, scale);
+                image.fill_rect(x, y, scale, scale, color);
+                for (sum, p) in sum.iter_mut().zip(&image.pixels) {
+                    for c in 0..3 {
+                        sum[c] += p[c] as u32;
+                    }
+                }
+            }
+            let mut image = background.clone();
+            for (p, sum) in image.pixels.iter_mut().zip(&sum) {
+                for c in 0..3 {
+                    p[c] = ((sum[c] + samples as u32 / 2) / samples as u32) as u8;
+                }
+            }
+            frames.push(image);
+        }
+        frames
+    }
+
+    fn tile_position(
+        &self,
+        board: &Board,
+        from: usize,
+        to: usize,
+        t: f64,
+        scale: usize,
+    ) -> (usize, usize) {
+        let t = self.easing.apply(t.clamp(0.0, 1.0));
+        let (fx, fy) = board.index_to_xy(from);
+        let (tx, ty) = board.index_to_xy(to);
+        let lerp = |a: usize, b: usize| {
+            ((a as f64 + (b as f64 - a as f64) * t) * scale as f64).round() as usize
+        };
+        (lerp(fx, tx), lerp(fy, ty))
+    }
+}
+
+#[test]
+fn test_animation() {
+    let mut board = Board::new(2, vec![0, 1, 2, 1]);
+    board.move_right();
+    let palette = Palette::new();
+    let animation = Animation {
+        frames_per_move: 4,
+        easing: Easing::Linear,
+        motion_blur: 1,
+    };
+    let frames = animation.render(&board, &palette, 4);
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[4], render_board(&board, &palette, 4));
+    // Halfway through the move the tile covers the two rightmost columns of
+    // the empty cell.
+    let white = palette.color(1, 2);
+    assert_eq!(frames[2].get(1, 0), [0, 0, 0]);
+    assert_eq!(frames[2].get(2, 0), white);
+    assert_eq!(frames[2].get(5, 0), white);
+    assert_eq!(frames[2].get(6, 0), [0, 0, 0]);
+}
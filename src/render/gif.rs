@@ -0,0 +1,182 @@
+use std::io::Write;
+
+use gif::{Encoder, EncodingError, Frame, Repeat};
+
+use super::{anim::Animation, render_board, replay, Image};
+use crate::{Board, Palette, Rgb};
+
+#[derive(Debug, Clone)]
+pub struct GifOptions {
+    /// Delay between frames in hundredths of a second.
+    pub delay: u16,
+    /// Size in pixels of one cell.
+    pub scale: usize,
+    /// Number of times the animation is played; `None` loops forever.
+    pub loop_count: Option<u16>,
+    /// Extra delay on the first frame in hundredths of a second.
+    pub first_hold: u16,
+    /// Extra delay on the last frame in hundredths of a second.
+    pub last_hold: u16,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            delay: 20,
+            scale: 4,
+            loop_count: None,
+            first_hold: 100,
+            last_hold: 200,
+        }
+    }
+}
+
+/// Writes the moves of `board.path` as an animated GIF, one frame per move.
+pub fn write_solution<W: Write>(
+    writer: W,
+    board: &Board,
+    palette: &Palette,
+    options: &GifOptions,
+) -> Result<(), EncodingError> {
+    let frames = replay(board)
+        .into_iter()
+        .map(|b| render_board(&b, palette, options.scale));
+    write_frames(writer, frames, &board_colors(board, palette), options)
+}
+
+/// Writes the moves of `board.path` as an animated GIF with the tiles sliding
+/// between cells. `options.delay` applies to every in-between frame.
+pub fn write_animation<W: Write>(
+    writer: W,
+    board: &Board,
+    palette: &Palette,
+    animation: &Animation,
+    options: &GifOptions,
+) -> Result<(), EncodingError> {
+    let frames = animation.frames(board, palette, options.scale);
+    write_frames(writer, frames, &board_colors(board, palette), options)
+}
+
+pub fn save_solution(
+    path: impl AsRef<std::path::Path>,
+    board: &Board,
+    palette: &Palette,
+    options: &GifOptions,
+) -> Result<(), EncodingError> {
+    let file = std::fs::File::create(path)?;
+    write_solution(std::io::BufWriter::new(file), board, palette, options)
+}
+
+/// The colours `render_board` draws the tiles of `board` with.
+fn board_colors(board: &Board, palette: &Palette) -> Vec<Rgb> {
+    let max_value = board.cells.iter().cloned().max().unwrap_or(0);
+    (0..=max_value)
+        .map(|v| palette.color(v, max_value))
+        .collect()
+}
+
+/// Writes `frames` as an animated GIF, encoding each frame as soon as it is
+/// produced. The frames must all have the same size.
+///
+/// `colors` make up the global palette, which holds at most 256 colours.
+/// Frames drawn only with these colours use it as they are; any other frame,
+/// such as one with motion blur, is quantized on its own.
+pub fn write_frames<W: Write>(
+    writer: W,
+    frames: impl IntoIterator<Item = Image>,
+    colors: &[Rgb],
+    options: &GifOptions,
+) -> Result<(), EncodingError> {
+    let mut frames = frames.into_iter().peekable();
+    let (width, height) = frames.peek().map_or((0, 0), |f| (f.width, f.height));
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "image is too large for GIF",
+            )
+            .into())
+        }
+    };
+
+    let mut colors = colors.to_vec();
+    colors.sort_unstable();
+    colors.dedup();
+    colors.truncate(256);
+    let global_palette: Vec<u8> = colors.iter().flatten().cloned().collect();
+
+    let mut encoder = Encoder::new(writer, width, height, &global_palette)?;
+    encoder.set_repeat(match options.loop_count {
+        Some(n) => Repeat::Finite(n),
+        None => Repeat::Infinite,
+    })?;
+    let mut first = true;
+    while let Some(image) = frames.next() {
+        let indices: Option<Vec<u8>> = image
+            .pixels
+            .iter()
+            .map(|p| colors.binary_search(p).ok().map(|i| i as u8))
+            .collect();
+        let mut frame = match indices {
+            Some(indices) => Frame::from_indexed_pixels(width, height, &indices, None),
+            None => {
+                let bytes: Vec<u8> = image.pixels.iter().flatten().cloned().collect();
+                Frame::from_rgb_speed(width, height, &bytes, 10)
+            }
+        };
+        frame.delay = options.delay;
+        if first {
+            frame.delay = frame.delay.saturating_add(options.first_hold);
+            first = false;
+        }
+        if frames.peek().is_none() {
+            frame.delay = frame.delay.saturating_add(options.last_hold);
+        }
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_write_solution() {
+    let goal = Board::new(2, vec![0, 1, 2, 1]);
+    let mut board = goal.clone();
+    board.move_right();
+    board.move_down();
+    let mut buf = vec![];
+    write_solution(&mut buf, &board, &Palette::new(), &GifOptions::default()).unwrap();
+
+    let mut decoder = gif::DecodeOptions::new().read_info(&buf[..]).unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (8, 8));
+    // One palette entry per tile value, padded to a power of two.
+    assert_eq!(decoder.global_palette().map(|p| p.len()), Some(4 * 3));
+    let mut delays = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert!(frame.palette.is_none());
+        delays.push(frame.delay);
+    }
+    assert_eq!(delays, vec![120, 20, 220]);
+
+    // Blurred frames have colours of their own and get local palettes.
+    let animation = Animation {
+        frames_per_move: 2,
+        motion_blur: 3,
+        ..Animation::default()
+    };
+    let mut buf = vec![];
+    write_animation(
+        &mut buf,
+        &board,
+        &Palette::new(),
+        &animation,
+        &GifOptions::default(),
+    )
+    .unwrap();
+    let mut decoder = gif::DecodeOptions::new().read_info(&buf[..]).unwrap();
+    let mut local_palettes = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        local_palettes.push(frame.palette.is_some());
+    }
+    assert_eq!(local_palettes, vec![false, true, true, true, true]);
+}
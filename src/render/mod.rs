@@ -0,0 +1,71 @@
+pub mod anim;
+pub mod gif;
+
+use crate::{Board, Palette, Rgb};
+
+/// An RGB raster image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0, 0, 0]; width * height],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[x + y * self.width]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[x + y * self.width] = color;
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.set(x, y, color);
+            }
+        }
+    }
+}
+
+/// Renders `board` with every cell drawn as a `scale` x `scale` square.
+pub fn render_board(board: &Board, palette: &Palette, scale: usize) -> Image {
+    let height = board.cells.len() / board.width;
+    let max_value = board.cells.iter().cloned().max().unwrap_or(0);
+    let mut image = Image::new(board.width * scale, height * scale);
+    for (i, c) in board.cells.iter().enumerate() {
+        let (x, y) = board.index_to_xy(i);
+        image.fill_rect(
+            x * scale,
+            y * scale,
+            scale,
+            scale,
+            palette.color(*c, max_value),
+        );
+    }
+    image
+}
+
+/// Returns the boards visited along `board.path`, from the start board to
+/// `board` itself.
+pub fn replay(board: &Board) -> Vec<Board> {
+    let mut board = board.clone();
+    let mut boards = vec![board.clone()];
+    while !board.path.is_empty() {
+        board.undo();
+        boards.push(board.clone());
+    }
+    boards.reverse();
+    boards
+}
@@ -1,11 +1,114 @@
 use rand_core::RngCore;
 
-use crate::{check_mate, Board};
+use crate::{
+    bits_per_cell, check_mate, format::Header, Board, BoardError, Heuristic, Move, PackedBoard,
+    Palette,
+};
 
+pub type ProgressFn = Box<dyn FnMut(&SearchProgress) + Send + Sync>;
+
+/// Snapshot passed to `Solver::progress` every `progress_interval` nodes.
+pub struct SearchProgress<'a> {
+    pub nodes: usize,
+    /// Boards waiting to be expanded, or the current depth for IDA*.
+    pub frontier: usize,
+    pub best_score: isize,
+    pub best_board: &'a Board,
+    /// Time since the first search on this solver.
+    pub elapsed: std::time::Duration,
+}
+
+/// Why the last search returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Solved,
+    NodeLimit,
+    Deadline,
+    Cancelled,
+    /// Nothing was left to search.
+    Exhausted,
+}
+
+/// Statistics accumulated over all searches on a solver. Heuristic and
+/// `check_mate` times are only measured with `Solver::measure_time`, and
+/// are summed over threads in parallel searches.
+#[derive(Debug, Clone, Default)]
+pub struct SearchReport {
+    pub expanded: usize,
+    pub generated: usize,
+    /// Generated boards dropped because they had been seen before.
+    pub duplicates: usize,
+    pub peak_frontier: usize,
+    pub peak_closed: usize,
+    pub heuristic_time: std::time::Duration,
+    pub check_mate_time: std::time::Duration,
+    pub elapsed: std::time::Duration,
+    pub solution_length: Option<usize>,
+    pub termination: Option<Termination>,
+}
+
+/// Stops a search from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// How `Solver::search` explores the boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Best-first by `score_fn`, finishing with `check_mate`. Fast, but the
+    /// moves are not the fewest possible.
+    #[default]
+    Greedy,
+    /// A* on moves made plus the heuristic. The result has the fewest moves
+    /// if the heuristic is admissible.
+    AStar,
+    /// Iterative deepening A*. Also optimal with an admissible heuristic,
+    /// and keeps only the current path in memory.
+    IdaStar,
+    /// Anytime weighted A*: each search returns a shorter solution than the
+    /// last, lowering `weight` by `weight_step` every time down to 1. With
+    /// an admissible heuristic, running out of boards proves the last
+    /// solution optimal.
+    Anytime,
+    /// Breadth-first by moves, keeping only the `beam_width` best boards by
+    /// `score_fn` in each layer, so memory stays bounded.
+    Beam,
+    /// Best-first by `score_fn` from the start and, backward, from the goal
+    /// at once, until the two searches meet.
+    Bidirectional,
+}
+
+/// Expansions between two looks at the clock for the deadline.
+pub(crate) const DEADLINE_INTERVAL: usize = 256;
+
+/// Layers of boards a beam search remembers to skip duplicates.
+const BEAM_HISTORY: usize = 16;
+
+/// A board waiting in a frontier, packed like the keys of the closed set.
+/// Its path is kept in the solver's `PathTree`.
 #[derive(Clone)]
 pub struct State {
-    board: Board,
-    score: isize,
+    pub(crate) board: PackedBoard,
+    /// Node of the last move in the `PathTree`.
+    pub(crate) node: u64,
+    pub(crate) moves: usize,
+    pub(crate) score: isize,
+    /// Heuristic value of `board`, if known, so that children can be
+    /// evaluated incrementally.
+    pub(crate) distance: Option<isize>,
 }
 
 pub struct Solver<R: RngCore> {
@@ -13,36 +116,321 @@ pub struct Solver<R: RngCore> {
     pub goal: Board,
     // pub states: Vec<State>,
     pub states: std::collections::BinaryHeap<State>,
-    pub closed: std::collections::HashSet<Vec<u8>>,
+    /// Boards seen so far, with the fewest moves they were reached in.
+    pub closed: std::collections::HashMap<PackedBoard, usize>,
+    /// The best board so far, by score in the greedy and beam searches and
+    /// by distance to the goal in the others.
     pub best_state: State,
     pub open_node_count: usize,
     pub open_node_limit: usize,
+    /// Searches give up, returning false, once this time has passed.
+    pub deadline: Option<std::time::Instant>,
+    pub cancel_token: Option<CancelToken>,
+    pub progress: Option<ProgressFn>,
+    pub progress_interval: usize,
     pub show_progress: bool,
+    /// Whether to time the heuristic and `check_mate` for the report, at the
+    /// cost of reading the clock around every call.
+    pub measure_time: bool,
     pub check_mate_cutoff: usize,
     pub random_walk: usize,
     pub random_walk_len: usize,
-    pub score_fn: Box<dyn Fn(&Board, isize) -> isize>,
-    pub distance_fn: Box<dyn Fn(&Board, &Board) -> isize>,
+    pub score_fn: Box<dyn Fn(&Board, isize) -> isize + Send + Sync>,
+    pub heuristic: Box<dyn Heuristic>,
+    pub mode: SearchMode,
+    /// Factor on the heuristic in `SearchMode::Anytime`.
+    pub weight: f64,
+    pub weight_step: f64,
+    /// Boards kept per layer in `SearchMode::Beam`.
+    pub beam_width: usize,
     pub result: Option<Board>,
+    pub report: SearchReport,
+    /// Header of the puzzle file the boards came from, if any.
+    pub header: Header,
+    /// Symbols and colours of the cell values, for showing boards.
+    pub palette: Palette,
+    pub(crate) start: Board,
+    /// Paths of the states in the frontiers.
+    pub(crate) paths: PathTree,
+    /// Bits per cell of the closed set's keys.
+    pub(crate) bits: u32,
+    pub(crate) beam_history: std::collections::VecDeque<std::collections::HashSet<PackedBoard>>,
+    pub(crate) bidirectional: Option<Bidirectional>,
+    pub(crate) started: Option<std::time::Instant>,
+    /// Node count at which `should_stop` next checks the deadline.
+    pub(crate) next_deadline_check: usize,
+}
+
+/// Node standing for the board a search began from, whose path is the
+/// prefix passed to `PathTree::restore`.
+pub(crate) const ROOT: u64 = u64::MAX;
+
+/// The paths of all states of a search as a tree of moves, each pointing to
+/// the move before it, so that states share their common prefixes.
+#[derive(Default)]
+pub(crate) struct PathTree {
+    /// Parent node and the empty cell before the move. Nodes are numbered
+    /// in 64 bits, as long searches can make more than 2^32 moves.
+    pub(crate) nodes: Vec<(u64, u32)>,
+}
+
+impl PathTree {
+    /// Returns the state of `board`, packed with `bits` bits per cell and
+    /// reached from the state with node `node` and `moves` moves; only the
+    /// moves made since are added to the tree.
+    pub(crate) fn state(
+        &mut self,
+        mut node: u64,
+        moves: usize,
+        board: &Board,
+        score: isize,
+        distance: Option<isize>,
+        bits: u32,
+    ) -> State {
+        for cell in &board.path[moves..] {
+            self.nodes.push((node, *cell as u32));
+            node = self.nodes.len() as u64 - 1;
+        }
+        State {
+            board: PackedBoard::new(board, bits),
+            node,
+            moves: board.path.len(),
+            score,
+            distance,
+        }
+    }
+
+    /// Sets the path of `board` to `prefix` followed by the moves up to
+    /// `node`.
+    pub(crate) fn restore(&self, mut node: u64, prefix: &[usize], board: &mut Board) {
+        board.path.clear();
+        while node != ROOT {
+            let (parent, cell) = self.nodes[node as usize];
+            board.path.push(cell as usize);
+            node = parent;
+        }
+        board.path.extend(prefix.iter().rev());
+        board.path.reverse();
+    }
+}
+
+/// The two searches of `SearchMode::Bidirectional`, forward first.
+pub(crate) struct Bidirectional {
+    /// The backward search estimates the distance to the start with its own
+    /// copy of the heuristic.
+    pub(crate) backward_heuristic: Box<dyn Heuristic>,
+    pub(crate) states: [std::collections::BinaryHeap<State>; 2],
+    /// Boards reached by each search, with their node in `Solver::paths`.
+    /// Paths of the backward search start at the goal.
+    pub(crate) seen: [std::collections::HashMap<PackedBoard, u64>; 2],
 }
 
 impl<R: RngCore> Solver<R> {
+    /// The board the search starts from.
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn set_heuristic(&mut self, mut heuristic: Box<dyn Heuristic>) {
+        heuristic.prepare(&self.goal);
+        self.heuristic = heuristic;
+        self.states = std::mem::take(&mut self.states)
+            .into_iter()
+            .map(|state| State {
+                distance: None,
+                ..state
+            })
+            .collect();
+    }
+
+    /// Replaces `score_fn` and rescores the boards waiting to be expanded
+    /// by the modes that use it, so that a resumed search does not order
+    /// its frontier by two functions at once.
+    pub fn set_score_fn(&mut self, score_fn: Box<dyn Fn(&Board, isize) -> isize + Send + Sync>) {
+        self.score_fn = score_fn;
+        match self.mode {
+            SearchMode::Greedy | SearchMode::Beam => {
+                let states = std::mem::take(&mut self.states);
+                let rescore =
+                    |state| self.rescore(state, &self.start.path, &*self.heuristic, &self.goal);
+                let best_state = rescore(self.best_state.clone());
+                self.states = states.into_iter().map(rescore).collect();
+                self.best_state = best_state;
+            }
+            SearchMode::Bidirectional => {
+                let Some(mut search) = self.bidirectional.take() else {
+                    return;
+                };
+                let [forward, backward] = std::mem::take(&mut search.states);
+                search.states = [
+                    forward
+                        .into_iter()
+                        .map(|state| self.rescore(state, &[], &*self.heuristic, &self.goal))
+                        .collect(),
+                    backward
+                        .into_iter()
+                        .map(|state| {
+                            self.rescore(state, &[], &*search.backward_heuristic, &self.start)
+                        })
+                        .collect(),
+                ];
+                self.bidirectional = Some(search);
+            }
+            SearchMode::AStar | SearchMode::IdaStar | SearchMode::Anytime => {}
+        }
+    }
+
+    /// Scores `state` with `score_fn`, estimating its distance to `target`
+    /// if it is not known yet.
+    fn rescore(
+        &self,
+        state: State,
+        prefix: &[usize],
+        heuristic: &dyn Heuristic,
+        target: &Board,
+    ) -> State {
+        let board = self.board(&state, prefix);
+        let distance = state
+            .distance
+            .unwrap_or_else(|| heuristic.evaluate(&board, target));
+        State {
+            score: (self.score_fn)(&board, distance),
+            distance: Some(distance),
+            ..state
+        }
+    }
+
+    /// Unpacks the board of `state` with its path, which begins with
+    /// `prefix`.
+    pub(crate) fn board(&self, state: &State, prefix: &[usize]) -> Board {
+        let mut board = self.unpack(&state.board);
+        self.paths.restore(state.node, prefix, &mut board);
+        board
+    }
+
+    /// Unpacks a board of this search, without a path.
+    pub(crate) fn unpack(&self, packed: &PackedBoard) -> Board {
+        Board::new(
+            self.goal.width,
+            packed.unpack(self.goal.cells.len(), self.bits),
+        )
+    }
+
+    /// Searches until a solution is found, storing it in `result`. Greedy
+    /// searches can be resumed to look for further solutions; the optimal
+    /// modes return false once they have found theirs.
     pub fn search(&mut self) -> bool {
+        let time = std::time::Instant::now();
+        self.started.get_or_insert(time);
+        let found = match self.mode {
+            SearchMode::Greedy => self.search_greedy(),
+            SearchMode::AStar => self.search_astar(),
+            SearchMode::IdaStar => self.search_ida_star(),
+            SearchMode::Anytime => self.search_anytime(),
+            SearchMode::Beam => self.search_beam(),
+            SearchMode::Bidirectional => self.search_bidirectional(),
+        };
+        self.finish_report(found, time.elapsed());
+        found
+    }
+
+    pub(crate) fn finish_report(&mut self, found: bool, elapsed: std::time::Duration) {
+        self.report.elapsed += elapsed;
+        self.report.solution_length = self.result.as_ref().map(|b| b.path.len());
+        self.report.termination = Some(if found {
+            Termination::Solved
+        } else {
+            self.stop_reason().unwrap_or(Termination::Exhausted)
+        });
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancel_token.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
+    pub(crate) fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+    }
+
+    fn stop_reason(&self) -> Option<Termination> {
+        if self.cancelled() {
+            Some(Termination::Cancelled)
+        } else if self.deadline_passed() {
+            Some(Termination::Deadline)
+        } else if self.open_node_count >= self.open_node_limit {
+            Some(Termination::NodeLimit)
+        } else {
+            None
+        }
+    }
+
+    /// Whether to stop before the next expansion. The clock is read only
+    /// every `DEADLINE_INTERVAL` expansions.
+    fn should_stop(&mut self) -> bool {
+        if self.open_node_count >= self.open_node_limit || self.cancelled() {
+            return true;
+        }
+        if self.open_node_count < self.next_deadline_check {
+            return false;
+        }
+        self.next_deadline_check = self.open_node_count + DEADLINE_INTERVAL;
+        self.deadline_passed()
+    }
+
+    /// Counts an expanded board and reports progress, given the sizes of the
+    /// frontier and the closed set.
+    fn count_expansion(&mut self, frontier: usize, closed: usize) {
+        self.open_node_count += 1;
+        self.report.expanded += 1;
+        self.report.peak_frontier = self.report.peak_frontier.max(frontier);
+        self.report.peak_closed = self.report.peak_closed.max(closed);
+        if self.progress.is_none() || self.open_node_count % self.progress_interval.max(1) != 0 {
+            return;
+        }
+        let best_board = self.board(&self.best_state, &self.start.path);
+        if let Some(progress) = self.progress.as_mut() {
+            progress(&SearchProgress {
+                nodes: self.open_node_count,
+                frontier,
+                best_score: self.best_state.score,
+                best_board: &best_board,
+                elapsed: self.started.map(|t| t.elapsed()).unwrap_or_default(),
+            });
+        }
+    }
+
+    fn search_greedy(&mut self) -> bool {
         while !self.states.is_empty() {
-            if self.open_node_count >= self.open_node_limit {
+            if self.should_stop() {
                 return false;
             }
-            let State { score, mut board } = self.states.pop().unwrap();
-            self.open_node_count += 1;
+            let state = self.states.pop().unwrap();
+            let mut board = self.board(&state, &self.start.path);
+            let State {
+                score,
+                node,
+                moves,
+                distance,
+                ..
+            } = state;
+            let distance = distance.unwrap_or_else(|| {
+                timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    self.heuristic.evaluate(&board, &self.goal)
+                })
+            });
+            self.count_expansion(self.states.len(), self.closed.len());
             if self.show_progress && self.open_node_count % 10000 == 0 {
-                let distance = (self.distance_fn)(&board, &self.goal);
                 println!(
                     "open_node_count: {:>6}, distance: {:>3}, current score: {:>5}, best score: {:>5}",
                     self.open_node_count, distance, score, self.best_state.score
                 );
-                self.best_state.board.print();
+                self.unpack(&self.best_state.board)
+                    .print_with(&self.palette);
             }
-            if let Some(result) = check_mate(&mut board, &self.goal, self.check_mate_cutoff) {
+            if let Some(result) = timed(self.measure_time, &mut self.report.check_mate_time, || {
+                check_mate(&mut board, &self.goal, self.check_mate_cutoff)
+            }) {
                 // if let Some(result) = crate::check_mate2(&mut board, &self.goal) {
                 self.result = Some(result);
                 return true;
@@ -51,20 +439,35 @@ impl<R: RngCore> Solver<R> {
             //     self.result = Some(board);
             //     return true;
             // }
-            let mut add_state = |board: Board| {
-                if self.closed.contains(&board.cells) {
-                    return;
-                } else {
-                    self.closed.insert(board.cells.clone());
+            // `moved` is the tile's move from the expanded board, if any, for
+            // incremental evaluation.
+            let mut add_state = |board: Board, moved: Option<(usize, usize)>| {
+                self.report.generated += 1;
+                match self.closed.entry(PackedBoard::new(&board, self.bits)) {
+                    std::collections::hash_map::Entry::Occupied(_) => {
+                        self.report.duplicates += 1;
+                        return;
+                    }
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(board.path.len());
+                    }
                 }
-                let score = (self.score_fn)(&board, (self.distance_fn)(&board, &self.goal));
+                let distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    moved
+                        .and_then(|(from, to)| {
+                            self.heuristic
+                                .update(&board, &self.goal, distance, from, to)
+                        })
+                        .unwrap_or_else(|| self.heuristic.evaluate(&board, &self.goal))
+                });
+                let score = (self.score_fn)(&board, distance);
+                let state = self
+                    .paths
+                    .state(node, moves, &board, score, Some(distance), self.bits);
                 if score > self.best_state.score {
-                    self.best_state = State {
-                        board: board.clone(),
-                        score,
-                    };
+                    self.best_state = state.clone();
                 }
-                self.states.push(State { board, score });
+                self.states.push(state);
             };
             let cs = board.move_candidates();
             let ps = [
@@ -75,53 +478,629 @@ impl<R: RngCore> Solver<R> {
             ];
             for i in 0..4 {
                 if cs[i] {
-                    let mut board = board.clone();
-                    board.move_to(ps[i]);
-                    add_state(board);
+                    let mut child = board.clone();
+                    child.move_to(ps[i]);
+                    add_state(child, Some((ps[i], board.empty_cell)));
                 }
             }
             for _ in 0..self.random_walk {
                 let mut board = board.clone();
                 board.shuffle(self.random_walk_len, &mut self.rng);
-                add_state(board);
+                add_state(board, None);
             }
             // self.states.sort_unstable_by_key(|s| s.score);
             // self.states.truncate(10000);
         }
         false
     }
+
+    fn search_astar(&mut self) -> bool {
+        while !self.states.is_empty() {
+            if self.should_stop() {
+                return false;
+            }
+            let state = self.states.pop().unwrap();
+            // Skip boards reached in fewer moves since they were queued.
+            if self
+                .closed
+                .get(&state.board)
+                .is_some_and(|m| *m < state.moves)
+            {
+                continue;
+            }
+            let board = self.board(&state, &self.start.path);
+            let State {
+                score,
+                node,
+                moves,
+                distance,
+                ..
+            } = state;
+            let distance = distance.unwrap_or_else(|| {
+                timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    self.heuristic.evaluate(&board, &self.goal)
+                })
+            });
+            self.count_expansion(self.states.len(), self.closed.len());
+            if self.show_progress && self.open_node_count % 10000 == 0 {
+                println!(
+                    "open_node_count: {:>6}, distance: {:>3}, moves: {:>4}, f: {:>4}",
+                    self.open_node_count, distance, moves, -score
+                );
+            }
+            if board.cells == self.goal.cells {
+                self.result = Some(board);
+                self.states.clear();
+                return true;
+            }
+            for next in Move::ALL.iter().filter_map(|mv| board.neighbor(*mv)) {
+                let mut child = board.clone();
+                child.move_to(next);
+                self.report.generated += 1;
+                let key = PackedBoard::new(&child, self.bits);
+                if self.closed.get(&key).is_some_and(|m| *m <= moves + 1) {
+                    self.report.duplicates += 1;
+                    continue;
+                }
+                self.closed.insert(key, moves + 1);
+                let distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    self.heuristic
+                        .update(&child, &self.goal, distance, next, board.empty_cell)
+                        .unwrap_or_else(|| self.heuristic.evaluate(&child, &self.goal))
+                });
+                let score = -(moves as isize + 1 + distance);
+                let state = self
+                    .paths
+                    .state(node, moves, &child, score, Some(distance), self.bits);
+                keep_closest(&mut self.best_state, &state);
+                self.states.push(state);
+            }
+        }
+        false
+    }
+
+    fn search_anytime(&mut self) -> bool {
+        let bound = self
+            .result
+            .as_ref()
+            .map_or(isize::MAX, |b| b.path.len() as isize);
+        while !self.states.is_empty() {
+            if self.should_stop() {
+                return false;
+            }
+            let state = self.states.pop().unwrap();
+            if self
+                .closed
+                .get(&state.board)
+                .is_some_and(|m| *m < state.moves)
+            {
+                continue;
+            }
+            let board = self.board(&state, &self.start.path);
+            let State {
+                node,
+                moves,
+                distance,
+                ..
+            } = state;
+            let distance = distance.unwrap_or_else(|| {
+                timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    self.heuristic.evaluate(&board, &self.goal)
+                })
+            });
+            // Nothing below can beat the current solution.
+            if moves as isize + distance >= bound {
+                continue;
+            }
+            self.count_expansion(self.states.len(), self.closed.len());
+            if self.show_progress && self.open_node_count % 10000 == 0 {
+                println!(
+                    "open_node_count: {:>6}, distance: {:>3}, moves: {:>4}, weight: {:.2}",
+                    self.open_node_count, distance, moves, self.weight
+                );
+            }
+            if board.cells == self.goal.cells {
+                self.result = Some(board);
+                self.weight = (self.weight - self.weight_step).max(1.0);
+                let weight = self.weight;
+                self.states = std::mem::take(&mut self.states)
+                    .into_iter()
+                    .map(|state| State {
+                        score: weighted_score(state.moves, state.distance.unwrap_or(0), weight),
+                        ..state
+                    })
+                    .collect();
+                return true;
+            }
+            for next in Move::ALL.iter().filter_map(|mv| board.neighbor(*mv)) {
+                let mut child = board.clone();
+                child.move_to(next);
+                self.report.generated += 1;
+                let key = PackedBoard::new(&child, self.bits);
+                if self.closed.get(&key).is_some_and(|m| *m <= moves + 1) {
+                    self.report.duplicates += 1;
+                    continue;
+                }
+                self.closed.insert(key, moves + 1);
+                let distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    self.heuristic
+                        .update(&child, &self.goal, distance, next, board.empty_cell)
+                        .unwrap_or_else(|| self.heuristic.evaluate(&child, &self.goal))
+                });
+                if moves as isize + 1 + distance < bound {
+                    let score = weighted_score(moves + 1, distance, self.weight);
+                    let state =
+                        self.paths
+                            .state(node, moves, &child, score, Some(distance), self.bits);
+                    keep_closest(&mut self.best_state, &state);
+                    self.states.push(state);
+                }
+            }
+        }
+        false
+    }
+
+    /// A search that stops in the middle of a layer keeps the rest of the
+    /// layer and the children found so far as its frontier.
+    fn search_beam(&mut self) -> bool {
+        // Children keep their paths, and the node and moves of their parent,
+        // until they make it into the next layer.
+        struct Child {
+            board: Board,
+            node: u64,
+            moves: usize,
+            score: isize,
+            distance: isize,
+        }
+
+        let bits = self.bits;
+        let into_state = |paths: &mut PathTree, c: Child| {
+            paths.state(c.node, c.moves, &c.board, c.score, Some(c.distance), bits)
+        };
+        let mut layer = std::mem::take(&mut self.states).into_vec();
+        while !layer.is_empty() {
+            let frontier = layer.len();
+            let mut next: Vec<Child> = vec![];
+            let mut seen = std::collections::HashSet::new();
+            let mut rest = layer.into_iter();
+            while let Some(state) = rest.next() {
+                if self.should_stop() {
+                    let next: Vec<State> = next
+                        .into_iter()
+                        .map(|c| into_state(&mut self.paths, c))
+                        .collect();
+                    self.states = std::iter::once(state).chain(rest).chain(next).collect();
+                    return false;
+                }
+                let mut board = self.board(&state, &self.start.path);
+                let State {
+                    node,
+                    moves,
+                    distance,
+                    ..
+                } = state;
+                let distance = distance.unwrap_or_else(|| {
+                    timed(self.measure_time, &mut self.report.heuristic_time, || {
+                        self.heuristic.evaluate(&board, &self.goal)
+                    })
+                });
+                self.count_expansion(frontier, self.beam_history.iter().map(|l| l.len()).sum());
+                if let Some(result) =
+                    timed(self.measure_time, &mut self.report.check_mate_time, || {
+                        check_mate(&mut board, &self.goal, self.check_mate_cutoff)
+                    })
+                {
+                    self.result = Some(result);
+                    let next: Vec<State> = next
+                        .into_iter()
+                        .map(|c| into_state(&mut self.paths, c))
+                        .collect();
+                    self.states = rest.chain(next).collect();
+                    return true;
+                }
+                for next_cell in Move::ALL.iter().filter_map(|mv| board.neighbor(*mv)) {
+                    let mut child = board.clone();
+                    child.move_to(next_cell);
+                    self.report.generated += 1;
+                    let key = PackedBoard::new(&child, self.bits);
+                    if self.beam_history.iter().any(|l| l.contains(&key)) || !seen.insert(key) {
+                        self.report.duplicates += 1;
+                        continue;
+                    }
+                    let distance =
+                        timed(self.measure_time, &mut self.report.heuristic_time, || {
+                            self.heuristic
+                                .update(&child, &self.goal, distance, next_cell, board.empty_cell)
+                                .unwrap_or_else(|| self.heuristic.evaluate(&child, &self.goal))
+                        });
+                    next.push(Child {
+                        score: (self.score_fn)(&child, distance),
+                        board: child,
+                        node,
+                        moves,
+                        distance,
+                    });
+                }
+            }
+            // Ties go to the board closer to the goal, then to the smaller
+            // cells so that runs are reproducible.
+            next.sort_by(|a, b| {
+                b.score
+                    .cmp(&a.score)
+                    .then(a.distance.cmp(&b.distance))
+                    .then_with(|| a.board.cells.cmp(&b.board.cells))
+            });
+            next.truncate(self.beam_width);
+            let next: Vec<State> = next
+                .into_iter()
+                .map(|c| into_state(&mut self.paths, c))
+                .collect();
+            if let Some(best) = next.first() {
+                if best.score > self.best_state.score {
+                    self.best_state = best.clone();
+                }
+            }
+            if self.show_progress {
+                println!(
+                    "open_node_count: {:>6}, moves: {:>4}, best score: {:>5}",
+                    self.open_node_count,
+                    next.first().map_or(0, |s| s.moves),
+                    self.best_state.score
+                );
+            }
+            self.beam_history
+                .push_back(next.iter().map(|s| s.board.clone()).collect());
+            if self.beam_history.len() > BEAM_HISTORY {
+                self.beam_history.pop_front();
+            }
+            layer = next;
+        }
+        false
+    }
+
+    fn search_bidirectional(&mut self) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        let mut search = match self.bidirectional.take() {
+            Some(search) => search,
+            None => {
+                let backward_heuristic = self.heuristic.for_goal(&self.start);
+                // Both searches record paths from where they began.
+                let root = |board: &Board| State {
+                    board: PackedBoard::new(board, self.bits),
+                    node: ROOT,
+                    moves: 0,
+                    score: isize::MIN,
+                    distance: None,
+                };
+                Bidirectional {
+                    backward_heuristic,
+                    states: [
+                        vec![root(&self.start)].into(),
+                        vec![root(&self.goal)].into(),
+                    ],
+                    seen: [
+                        [(PackedBoard::new(&self.start, self.bits), ROOT)].into(),
+                        [(PackedBoard::new(&self.goal, self.bits), ROOT)].into(),
+                    ],
+                }
+            }
+        };
+        if self.start.cells == self.goal.cells {
+            self.result = Some(self.start.clone());
+            return true;
+        }
+
+        loop {
+            // Expand the side with the smaller frontier.
+            let side = (search.states[1].len() < search.states[0].len()) as usize;
+            if self.should_stop() {
+                self.bidirectional = Some(search);
+                return false;
+            }
+            let Some(state) = search.states[side].pop() else {
+                // One side has seen all its boards without meeting the other;
+                // kept so that later searches report the same.
+                self.bidirectional = Some(search);
+                return false;
+            };
+            let board = self.board(&state, &[]);
+            let State {
+                node,
+                moves,
+                distance,
+                ..
+            } = state;
+            self.count_expansion(
+                search.states[0].len() + search.states[1].len(),
+                search.seen[0].len() + search.seen[1].len(),
+            );
+            let (heuristic, target) = if side == 0 {
+                (&self.heuristic, &self.goal)
+            } else {
+                (&search.backward_heuristic, &self.start)
+            };
+            let distance = distance.unwrap_or_else(|| {
+                timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    heuristic.evaluate(&board, target)
+                })
+            });
+            if self.show_progress && self.open_node_count % 10000 == 0 {
+                println!(
+                    "open_node_count: {:>6}, distance: {:>3}, forward: {:>6}, backward: {:>6}",
+                    self.open_node_count,
+                    distance,
+                    search.seen[0].len(),
+                    search.seen[1].len()
+                );
+            }
+            for next in Move::ALL.iter().filter_map(|mv| board.neighbor(*mv)) {
+                let mut child = board.clone();
+                child.move_to(next);
+                self.report.generated += 1;
+                let key = PackedBoard::new(&child, self.bits);
+                if search.seen[side].contains_key(&key) {
+                    self.report.duplicates += 1;
+                    continue;
+                }
+                if let Some(other) = search.seen[1 - side].get(&key) {
+                    let mut other_board = child.clone();
+                    self.paths.restore(*other, &[], &mut other_board);
+                    let (forward, backward) = if side == 0 {
+                        (child, other_board)
+                    } else {
+                        (other_board, child)
+                    };
+                    self.result = Some(self.splice(&forward, &backward));
+                    return true;
+                }
+                let distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+                    heuristic
+                        .update(&child, target, distance, next, board.empty_cell)
+                        .unwrap_or_else(|| heuristic.evaluate(&child, target))
+                });
+                let score = (self.score_fn)(&child, distance);
+                let state = self
+                    .paths
+                    .state(node, moves, &child, score, Some(distance), self.bits);
+                if side == 0 {
+                    keep_closest(&mut self.best_state, &state);
+                }
+                search.seen[side].insert(key, state.node);
+                search.states[side].push(state);
+            }
+        }
+    }
+
+    /// Joins the paths from the start to `forward` and from the goal to
+    /// `backward`, the same board, into one board solved from the start.
+    fn splice(&self, forward: &Board, backward: &Board) -> Board {
+        let mut board = self.start.clone();
+        // The first empty cell is the start's own.
+        for empty_cell in forward
+            .path
+            .iter()
+            .chain(std::iter::once(&forward.empty_cell))
+            .chain(backward.path.iter().rev())
+            .skip(1)
+        {
+            board.move_to(*empty_cell);
+        }
+        board
+    }
+
+    fn search_ida_star(&mut self) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        let mut board = self.start.clone();
+        let distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+            self.heuristic.evaluate(&board, &self.goal)
+        });
+        let mut bound = distance;
+        loop {
+            match self.bounded_search(&mut board, 0, distance, bound) {
+                Bounded::Found => {
+                    self.result = Some(board);
+                    return true;
+                }
+                Bounded::Exceeded(next) if next != isize::MAX => bound = next,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Depth-first search below `board`, pruning boards whose moves plus
+    /// heuristic exceed `bound`. On success `board` is left solved.
+    fn bounded_search(
+        &mut self,
+        board: &mut Board,
+        moves: isize,
+        distance: isize,
+        bound: isize,
+    ) -> Bounded {
+        let f = moves + distance;
+        if f > bound {
+            return Bounded::Exceeded(f);
+        }
+        if board.cells == self.goal.cells {
+            return Bounded::Found;
+        }
+        if self.should_stop() {
+            return Bounded::LimitReached;
+        }
+        if self.best_state.distance.is_none_or(|best| distance < best) {
+            // Only improvements go into the path tree, as the search itself
+            // keeps just the current path.
+            let state = self.paths.state(
+                ROOT,
+                self.start.path.len(),
+                board,
+                -f,
+                Some(distance),
+                self.bits,
+            );
+            self.best_state = state;
+        }
+        self.count_expansion(moves as usize, 0);
+        if self.show_progress && self.open_node_count % 10000 == 0 {
+            println!(
+                "open_node_count: {:>6}, distance: {:>3}, moves: {:>4}, bound: {:>4}",
+                self.open_node_count, distance, moves, bound
+            );
+        }
+
+        let mut next_bound = isize::MAX;
+        let previous = board.path.last().cloned();
+        for mv in Move::ALL {
+            let Some(next) = board.neighbor(mv) else {
+                continue;
+            };
+            // Moving straight back never helps.
+            if Some(next) == previous {
+                continue;
+            }
+            let empty_cell = board.empty_cell;
+            board.move_to(next);
+            self.report.generated += 1;
+            let child_distance = timed(self.measure_time, &mut self.report.heuristic_time, || {
+                self.heuristic
+                    .update(board, &self.goal, distance, next, empty_cell)
+                    .unwrap_or_else(|| self.heuristic.evaluate(board, &self.goal))
+            });
+            match self.bounded_search(board, moves + 1, child_distance, bound) {
+                Bounded::Found => return Bounded::Found,
+                Bounded::LimitReached => return Bounded::LimitReached,
+                Bounded::Exceeded(f) => next_bound = next_bound.min(f),
+            }
+            board.undo();
+        }
+        Bounded::Exceeded(next_bound)
+    }
+}
+
+/// Replaces `best` with `state` if it is closer to the goal, for the modes
+/// whose scores do not tell how close a board is.
+fn keep_closest(best: &mut State, state: &State) {
+    if state
+        .distance
+        .is_some_and(|d| best.distance.is_none_or(|b| d < b))
+    {
+        *best = state.clone();
+    }
+}
+
+/// Runs `f`, adding the time it took to `total` if `enabled`.
+pub(crate) fn timed<T>(enabled: bool, total: &mut std::time::Duration, f: impl FnOnce() -> T) -> T {
+    if !enabled {
+        return f();
+    }
+    let time = std::time::Instant::now();
+    let result = f();
+    *total += time.elapsed();
+    result
+}
+
+fn weighted_score(moves: usize, distance: isize, weight: f64) -> isize {
+    // Scaled so that fractional weights still order states finely.
+    -((moves as f64 + weight * distance as f64) * 1000.0) as isize
+}
+
+enum Bounded {
+    Found,
+    /// Smallest moves plus heuristic beyond the bound, or `isize::MAX` if
+    /// nothing was cut off.
+    Exceeded(isize),
+    LimitReached,
 }
 
 impl Solver<rand_pcg::Lcg64Xsh32> {
     pub fn new(goal: Board, board: Board) -> Self {
-        let mut c1 = goal.cells.clone();
-        c1.sort_unstable();
-        let mut c2 = board.cells.clone();
-        c2.sort_unstable();
-        assert!(c1 == c2);
+        Self::try_new(goal, board).unwrap()
+    }
+
+    pub fn try_new(goal: Board, board: Board) -> Result<Self, BoardError> {
+        goal.check_compatible(&board)?;
+        if !board.is_reachable_from(&goal) {
+            return Err(BoardError::Unreachable);
+        }
 
         let seed = 0;
         let rng = rand_pcg::Pcg32::new(seed, 0xa02bdbf7bb3c0a7);
-        let state = State {
-            board,
-            score: isize::MIN,
-        };
-        Self {
+        let bits = bits_per_cell(&goal);
+        let mut paths = PathTree::default();
+        let state = paths.state(ROOT, board.path.len(), &board, isize::MIN, None, bits);
+        let mut heuristic: Box<dyn Heuristic> = Box::new(crate::Distance2::default());
+        heuristic.prepare(&goal);
+        Ok(Self {
             rng,
             goal,
-            closed: vec![state.board.cells.clone()].into_iter().collect(),
+            closed: [(PackedBoard::new(&board, bits), board.path.len())].into(),
             states: vec![state.clone()].into(),
             best_state: state,
             open_node_count: 0,
             open_node_limit: usize::MAX,
+            deadline: None,
+            cancel_token: None,
+            progress: None,
+            progress_interval: 10000,
             show_progress: false,
+            measure_time: false,
             check_mate_cutoff: 10,
             random_walk: 1,
             random_walk_len: 10,
             score_fn: Box::new(|_, distance| -distance),
-            distance_fn: Box::new(crate::compute_distance2),
+            heuristic,
+            mode: SearchMode::default(),
+            weight: 3.0,
+            weight_step: 0.5,
+            beam_width: 1000,
             result: None,
+            report: SearchReport::default(),
+            header: Header::default(),
+            palette: Palette::new(),
+            start: board,
+            paths,
+            bits,
+            beam_history: Default::default(),
+            bidirectional: None,
+            started: None,
+            next_deadline_check: 0,
+        })
+    }
+}
+
+impl std::fmt::Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Termination::Solved => "solved",
+            Termination::NodeLimit => "node limit reached",
+            Termination::Deadline => "deadline passed",
+            Termination::Cancelled => "cancelled",
+            Termination::Exhausted => "search space exhausted",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::fmt::Display for SearchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "expanded:        {}", self.expanded)?;
+        writeln!(f, "generated:       {}", self.generated)?;
+        writeln!(f, "duplicates:      {}", self.duplicates)?;
+        writeln!(f, "peak frontier:   {}", self.peak_frontier)?;
+        writeln!(f, "peak closed:     {}", self.peak_closed)?;
+        writeln!(f, "heuristic time:  {:?}", self.heuristic_time)?;
+        writeln!(f, "check_mate time: {:?}", self.check_mate_time)?;
+        writeln!(f, "elapsed:         {:?}", self.elapsed)?;
+        if let Some(length) = self.solution_length {
+            writeln!(f, "solution length: {}", length)?;
+        }
+        if let Some(termination) = self.termination {
+            writeln!(f, "termination:     {}", termination)?;
         }
+        Ok(())
     }
 }
 
@@ -144,3 +1123,238 @@ impl Ord for State {
         self.score.cmp(&other.score)
     }
 }
+
+#[test]
+fn test_optimal_search() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(3, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    for _ in 0..5 {
+        let mut board = goal.clone();
+        board.shuffle(30, &mut rng);
+        board.path.clear();
+
+        let mut lengths = vec![];
+        for mode in [SearchMode::AStar, SearchMode::IdaStar] {
+            let mut solver = Solver::new(goal.clone(), board.clone());
+            solver.set_heuristic(Box::new(crate::Manhattan::default()));
+            solver.mode = mode;
+            assert!(solver.search());
+            assert!(!solver.search());
+            let result = solver.result.unwrap();
+            assert_eq!(result.cells, goal.cells);
+            lengths.push(result.path.len());
+        }
+        // Breadth-first search gives the true distance.
+        let mut seen = std::collections::HashSet::from([board.cells.to_vec()]);
+        let mut frontier = vec![board.clone()];
+        let mut depth = 0;
+        while !frontier.iter().any(|b| b.cells == goal.cells) {
+            let mut next = vec![];
+            for b in frontier {
+                for cell in Move::ALL.iter().filter_map(|mv| b.neighbor(*mv)) {
+                    let mut child = b.clone();
+                    child.move_to(cell);
+                    if seen.insert(child.cells.to_vec()) {
+                        next.push(child);
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        assert_eq!(lengths, [depth, depth]);
+
+        let mut solver = Solver::new(goal.clone(), board.clone());
+        solver.set_heuristic(Box::new(crate::Manhattan::default()));
+        solver.mode = SearchMode::Anytime;
+        let mut last = usize::MAX;
+        while solver.search() {
+            let length = solver.result.as_ref().unwrap().path.len();
+            assert!(length < last);
+            last = length;
+        }
+        assert_eq!(last, depth);
+    }
+}
+
+#[test]
+fn test_beam_search() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    board.shuffle(300, &mut rng);
+    board.path.clear();
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.mode = SearchMode::Beam;
+    solver.beam_width = 50;
+    solver.open_node_limit = 520;
+    assert!(!solver.search());
+    assert_eq!(solver.open_node_count, 520);
+    assert!(solver.report.peak_frontier <= 50);
+    // Stopped in the middle of a layer: the unexpanded part of the layer is
+    // kept along with the children found so far.
+    assert!(solver.states.len() > 50);
+
+    solver.open_node_limit = usize::MAX;
+    assert!(solver.search());
+    let result = solver.result.clone().unwrap();
+    crate::verify(&board, &goal, &result.moves()).unwrap();
+    assert!(!solver.beam_history.is_empty());
+    assert!(solver.beam_history.iter().all(|l| l.len() <= 50));
+    assert!(!solver.states.is_empty());
+}
+
+#[test]
+fn test_bidirectional_search() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    for _ in 0..10 {
+        let mut board = goal.clone();
+        board.shuffle(100, &mut rng);
+        board.path.clear();
+
+        let mut solver = Solver::new(goal.clone(), board.clone());
+        solver.mode = SearchMode::Bidirectional;
+        assert!(solver.search());
+        let result = solver.result.unwrap();
+        crate::verify(&board, &goal, &result.moves()).unwrap();
+    }
+
+    // Two tiles swapped cannot be reached on a 2x2 board.
+    let mut solver = Solver::new(
+        Board::new(2, vec![0, 1, 2, 3]),
+        Board::new(2, vec![0, 1, 2, 3]),
+    );
+    solver.goal = Board::new(2, vec![0, 2, 1, 3]);
+    solver.set_heuristic(Box::new(crate::Distance2::default()));
+    solver.mode = SearchMode::Bidirectional;
+    assert!(!solver.search());
+    assert_eq!(solver.report.termination, Some(Termination::Exhausted));
+    let nodes = solver.open_node_count;
+    assert!(!solver.search());
+    assert_eq!(solver.report.termination, Some(Termination::Exhausted));
+    assert_eq!(solver.open_node_count, nodes);
+}
+
+#[test]
+fn test_search_control() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    board.shuffle(300, &mut rng);
+    board.path.clear();
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    let token = CancelToken::new();
+    solver.cancel_token = Some(token.clone());
+    token.cancel();
+    assert!(!solver.search());
+    assert_eq!(solver.open_node_count, 0);
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.deadline = Some(std::time::Instant::now());
+    assert!(!solver.search());
+
+    let nodes = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.progress_interval = 10;
+    solver.progress = Some(Box::new({
+        let nodes = nodes.clone();
+        move |progress| nodes.lock().unwrap().push(progress.nodes)
+    }));
+    assert!(solver.search());
+    let nodes = nodes.lock().unwrap();
+    assert_eq!(nodes.len(), solver.open_node_count / 10);
+    assert!(nodes.iter().enumerate().all(|(i, n)| *n == (i + 1) * 10));
+}
+
+#[test]
+fn test_optimal_search_progress() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    board.shuffle(40, &mut rng);
+    board.path.clear();
+    let mut manhattan = crate::Manhattan::default();
+    manhattan.prepare(&goal);
+
+    for mode in [
+        SearchMode::AStar,
+        SearchMode::IdaStar,
+        SearchMode::Anytime,
+        SearchMode::Bidirectional,
+    ] {
+        let best_boards = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut solver = Solver::new(goal.clone(), board.clone());
+        solver.mode = mode;
+        solver.set_heuristic(Box::new(crate::Manhattan::default()));
+        solver.progress_interval = 5;
+        solver.progress = Some(Box::new({
+            let best_boards = best_boards.clone();
+            move |progress| {
+                best_boards
+                    .lock()
+                    .unwrap()
+                    .push(progress.best_board.clone())
+            }
+        }));
+        assert!(solver.search());
+        // The boards reported come ever closer to the goal along real paths.
+        let best_boards = best_boards.lock().unwrap();
+        let distances: Vec<isize> = best_boards
+            .iter()
+            .map(|best| {
+                let mut replayed = board.clone();
+                replayed.apply_moves(&best.moves()).unwrap();
+                assert_eq!(replayed.cells, best.cells, "{:?}", mode);
+                manhattan.evaluate(best, &goal)
+            })
+            .collect();
+        assert!(!distances.is_empty(), "{:?}", mode);
+        assert!(distances.windows(2).all(|w| w[1] <= w[0]), "{:?}", mode);
+        assert!(
+            distances.last() < Some(&manhattan.evaluate(&board, &goal)),
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[test]
+fn test_search_report() {
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+    let mut board = goal.clone();
+    board.shuffle(300, &mut rng);
+    board.path.clear();
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.open_node_limit = 5;
+    assert!(!solver.search());
+    assert_eq!(solver.report.termination, Some(Termination::NodeLimit));
+    assert_eq!(solver.report.expanded, 5);
+    assert!(solver.report.check_mate_time.is_zero());
+
+    solver.open_node_limit = usize::MAX;
+    solver.measure_time = true;
+    assert!(solver.search());
+    let report = &solver.report;
+    assert!(!report.check_mate_time.is_zero());
+    assert_eq!(report.termination, Some(Termination::Solved));
+    assert_eq!(report.expanded, solver.open_node_count);
+    assert!(report.generated > report.duplicates);
+    assert_eq!(report.peak_closed, solver.closed.len());
+    assert_eq!(
+        report.solution_length,
+        solver.result.as_ref().map(|b| b.path.len())
+    );
+
+    let mut solver = Solver::new(goal.clone(), board.clone());
+    solver.mode = SearchMode::AStar;
+    solver.set_heuristic(Box::new(crate::Manhattan::default()));
+    solver.cancel_token = Some(CancelToken::new());
+    solver.cancel_token.as_ref().unwrap().cancel();
+    assert!(!solver.search());
+    assert_eq!(solver.report.termination, Some(Termination::Cancelled));
+}
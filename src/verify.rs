@@ -0,0 +1,61 @@
+use crate::{Board, BoardError, Move, MoveError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The start and goal boards cannot belong to the same puzzle.
+    Board(BoardError),
+    /// The move at `index` would take the empty cell off the board.
+    IllegalMove { index: usize, mv: Move },
+    /// All moves are legal but the last board differs from the goal.
+    NotSolved,
+}
+
+/// Replays `moves` from `start` and checks that they end on `goal`.
+///
+/// Returns the final board, whose `path` holds the replayed moves.
+pub fn verify(start: &Board, goal: &Board, moves: &[Move]) -> Result<Board, VerifyError> {
+    start.check_compatible(goal).map_err(VerifyError::Board)?;
+    let mut board = start.clone();
+    board.path.clear();
+    board.apply_moves(moves).map_err(|e| match e {
+        MoveError::IllegalMove { index, mv } => VerifyError::IllegalMove { index, mv },
+        MoveError::InvalidNotation { .. } => unreachable!(),
+    })?;
+    if board.cells != goal.cells {
+        return Err(VerifyError::NotSolved);
+    }
+    Ok(board)
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VerifyError::Board(e) => write!(f, "{}", e),
+            VerifyError::IllegalMove { index, mv } => {
+                write!(f, "move {} ({}) is illegal", index, mv)
+            }
+            VerifyError::NotSolved => write!(f, "moves do not reach the goal"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+#[test]
+fn test_verify() {
+    let goal = Board::new(2, vec![0, 1, 2, 3]);
+    let start = Board::new(2, vec![1, 3, 0, 2]);
+    let moves = crate::parse_moves("RUL").unwrap();
+    assert_eq!(verify(&start, &goal, &moves).unwrap().path.len(), 3);
+    assert_eq!(
+        verify(&start, &goal, &moves[..2]).err(),
+        Some(VerifyError::NotSolved)
+    );
+    assert_eq!(
+        verify(&start, &goal, &[Move::Down]).err(),
+        Some(VerifyError::IllegalMove {
+            index: 0,
+            mv: Move::Down
+        })
+    );
+}
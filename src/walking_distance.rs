@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{Board, Heuristic};
+
+/// States kept per direction before the breadth-first search stops at the
+/// end of a level.
+const TABLE_LIMIT: usize = 1 << 20;
+
+/// Counts vertical and horizontal moves separately, each on a relaxed puzzle
+/// that only tracks how many tiles of each class sit in every row (or
+/// column) and lets the empty cell swap with any tile of the next row.
+///
+/// A colour whose goal cells all lie in one row shares that row's class, as
+/// in the classic walking distance; every other colour is a class of its own.
+/// Tiles of a class are interchangeable, so duplicate colours are fine.
+#[derive(Default)]
+pub struct WalkingDistance {
+    /// Built on first use if `prepare` was not called, like `horizontal`.
+    vertical: OnceLock<Table>,
+    horizontal: OnceLock<Table>,
+}
+
+#[derive(Default)]
+struct Table {
+    transpose: bool,
+    /// Class of every colour.
+    classes: Vec<usize>,
+    class_count: usize,
+    distances: HashMap<Vec<u16>, u16>,
+    /// Every state closer to the goal than this is in `distances`.
+    horizon: u16,
+}
+
+impl Table {
+    fn new(goal: &Board, transpose: bool) -> Self {
+        let mut table = Table {
+            transpose,
+            ..Default::default()
+        };
+        let lines = table.lines(goal);
+        let mut goal_lines: Vec<Option<Option<usize>>> = vec![None; 256];
+        for (i, c) in goal.cells.iter().enumerate().filter(|(_, c)| **c != 0) {
+            let line = table.line_of(goal, i);
+            let l = &mut goal_lines[*c as usize];
+            *l = match *l {
+                None => Some(Some(line)),
+                Some(Some(l)) if l == line => Some(Some(l)),
+                Some(_) => Some(None),
+            };
+        }
+        table.class_count = lines;
+        table.classes = goal_lines
+            .into_iter()
+            .map(|l| match l {
+                Some(Some(line)) => line,
+                Some(None) => {
+                    table.class_count += 1;
+                    table.class_count - 1
+                }
+                None => 0,
+            })
+            .collect();
+        table.search(table.state(goal), lines);
+        table
+    }
+
+    fn lines(&self, board: &Board) -> usize {
+        if self.transpose {
+            board.width
+        } else {
+            board.cells.len() / board.width
+        }
+    }
+
+    fn line_of(&self, board: &Board, index: usize) -> usize {
+        let (x, y) = board.index_to_xy(index);
+        if self.transpose {
+            x
+        } else {
+            y
+        }
+    }
+
+    /// Tile counts per line and class, followed by the line of the empty
+    /// cell.
+    fn state(&self, board: &Board) -> Vec<u16> {
+        let lines = self.lines(board);
+        let mut state = vec![0; lines * self.class_count + 1];
+        for (i, c) in board.cells.iter().enumerate() {
+            if *c != 0 {
+                state[self.line_of(board, i) * self.class_count + self.classes[*c as usize]] += 1;
+            }
+        }
+        state[lines * self.class_count] = self.line_of(board, board.empty_cell) as u16;
+        state
+    }
+
+    fn search(&mut self, goal: Vec<u16>, lines: usize) {
+        let classes = self.class_count;
+        self.distances.insert(goal.clone(), 0);
+        let mut frontier = vec![goal];
+        let mut depth = 0;
+        while !frontier.is_empty() && self.distances.len() < TABLE_LIMIT {
+            let mut next = vec![];
+            for state in &frontier {
+                let empty = state[lines * classes] as usize;
+                let adjacent = [empty.checked_sub(1), Some(empty + 1).filter(|l| *l < lines)];
+                for line in adjacent.into_iter().flatten() {
+                    for class in 0..classes {
+                        if state[line * classes + class] == 0 {
+                            continue;
+                        }
+                        let mut moved = state.clone();
+                        moved[line * classes + class] -= 1;
+                        moved[empty * classes + class] += 1;
+                        moved[lines * classes] = line as u16;
+                        if !self.distances.contains_key(&moved) {
+                            self.distances.insert(moved.clone(), depth + 1);
+                            next.push(moved);
+                        }
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        // States beyond the last complete level are at least one further.
+        self.horizon = depth + 1;
+    }
+
+    fn evaluate(&self, board: &Board) -> isize {
+        *self
+            .distances
+            .get(&self.state(board))
+            .unwrap_or(&self.horizon) as isize
+    }
+}
+
+impl Heuristic for WalkingDistance {
+    fn name(&self) -> &'static str {
+        "walking_distance"
+    }
+
+    fn is_admissible(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, goal: &Board) {
+        self.vertical = OnceLock::from(Table::new(goal, false));
+        self.horizontal = OnceLock::from(Table::new(goal, true));
+    }
+
+    fn for_goal(&self, goal: &Board) -> Box<dyn Heuristic> {
+        let mut heuristic = WalkingDistance::default();
+        heuristic.prepare(goal);
+        Box::new(heuristic)
+    }
+
+    fn evaluate(&self, board: &Board, goal: &Board) -> isize {
+        let vertical = self.vertical.get_or_init(|| Table::new(goal, false));
+        let horizontal = self.horizontal.get_or_init(|| Table::new(goal, true));
+        vertical.evaluate(board) + horizontal.evaluate(board)
+    }
+}
+
+#[test]
+fn test_walking_distance() {
+    let mut heuristic = WalkingDistance::default();
+    let goal = Board::new(3, vec![1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    heuristic.prepare(&goal);
+    assert_eq!(heuristic.evaluate(&goal, &goal), 0);
+    // The relaxed 8-puzzle is small enough to search completely.
+    assert!(heuristic.vertical.get().unwrap().distances.len() < TABLE_LIMIT);
+
+    let goal = Board::new(4, vec![0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 1, 4, 4]);
+    heuristic.prepare(&goal);
+    let mut rng = rand_pcg::Pcg32::new(0, 0xa02bdbf7bb3c0a7);
+    let mut board = goal.clone();
+    board.shuffle(1, &mut rng);
+    assert_eq!(heuristic.evaluate(&board, &goal), 1);
+    for _ in 0..100 {
+        board.shuffle(1, &mut rng);
+        assert!(heuristic.evaluate(&board, &goal) <= board.path.len() as isize);
+    }
+}